@@ -0,0 +1,220 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::error::{AnthropicError, ApiErrorResponse};
+
+use super::{
+    message::{MessageRequest, MessageResponse},
+    stream::BodyDecoder,
+};
+
+/// A request of a batch, identified by `custom_id` in the results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    /// Unique id of the request within the batch, used to match results to requests.
+    pub custom_id: String,
+    pub params: MessageRequest,
+}
+
+impl BatchRequest {
+    pub fn new(custom_id: impl Into<String>, params: MessageRequest) -> Self {
+        Self {
+            custom_id: custom_id.into(),
+            params,
+        }
+    }
+}
+
+/// A batch of message requests processed asynchronously, as returned by [`Client::create_batch`].
+///
+/// [`Client::create_batch`]: crate::client::Client::create_batch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MessageBatch {
+    pub id: String,
+    pub processing_status: BatchStatus,
+    pub request_counts: BatchRequestCounts,
+    /// RFC 3339 datetime of the batch creation.
+    pub created_at: String,
+    /// RFC 3339 datetime after which unprocessed requests expire.
+    pub expires_at: String,
+    pub ended_at: Option<String>,
+    pub cancel_initiated_at: Option<String>,
+    pub archived_at: Option<String>,
+    /// URL of the results, only set once processing has ended.
+    pub results_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+/// Number of requests of a batch by outcome, all requests are `processing` until the batch ends.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchRequestCounts {
+    pub processing: u32,
+    pub succeeded: u32,
+    pub errored: u32,
+    pub canceled: u32,
+    pub expired: u32,
+}
+
+/// A page of batches, most recently created first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchList {
+    pub data: Vec<MessageBatch>,
+    /// Whether more batches follow this page.
+    pub has_more: bool,
+    /// Id of the first batch of the page, to pass as `before_id` for the previous page.
+    pub first_id: Option<String>,
+    /// Id of the last batch of the page, to pass as `after_id` for the next page.
+    pub last_id: Option<String>,
+}
+
+/// Pagination parameters of [`Client::list_batches_page`].
+///
+/// [`Client::list_batches_page`]: crate::client::Client::list_batches_page
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ListBatchesParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_id: Option<String>,
+    /// Number of batches per page, between 1 and 1000. Defaults to 20.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl ListBatchesParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_before_id(mut self, before_id: impl Into<String>) -> Self {
+        self.before_id = Some(before_id.into());
+        self
+    }
+
+    pub fn with_after_id(mut self, after_id: impl Into<String>) -> Self {
+        self.after_id = Some(after_id.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Outcome of a single request of a batch, as yielded by [`Client::batch_results`].
+///
+/// [`Client::batch_results`]: crate::client::Client::batch_results
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct BatchResult {
+    pub custom_id: String,
+    pub result: BatchOutcome,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Succeeded { message: MessageResponse },
+    Errored { error: ApiErrorResponse },
+    Canceled,
+    Expired,
+}
+
+/// Splits a JSON Lines body received in chunks into parsed values.
+pub(crate) struct JsonLinesDecoder<T> {
+    buffer: Vec<u8>,
+    item: PhantomData<T>,
+}
+
+impl<T> JsonLinesDecoder<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> BodyDecoder for JsonLinesDecoder<T> {
+    type Item = T;
+
+    /// Parses the lines completed by `chunk`, keeping the trailing partial line for later.
+    fn decode(&mut self, chunk: &[u8]) -> Vec<Result<T, AnthropicError>> {
+        self.buffer.extend_from_slice(chunk);
+        let Some(end) = self.buffer.iter().rposition(|byte| *byte == b'\n') else {
+            return Vec::new();
+        };
+        let lines: Vec<u8> = self.buffer.drain(..=end).collect();
+        lines
+            .split(|byte| *byte == b'\n')
+            .filter_map(parse_line)
+            .collect()
+    }
+
+    /// Parses the last line, which may not end with a newline.
+    fn finish(&mut self) -> Vec<Result<T, AnthropicError>> {
+        let line = std::mem::take(&mut self.buffer);
+        parse_line(&line).into_iter().collect()
+    }
+}
+
+fn parse_line<T: DeserializeOwned>(line: &[u8]) -> Option<Result<T, AnthropicError>> {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return None;
+    }
+    Some(serde_json::from_slice(line).map_err(AnthropicError::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiErrorType;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_deserialize_message_batch() {
+        let raw = r#"{"id":"msgbatch_01","type":"message_batch","processing_status":"in_progress","request_counts":{"processing":2,"succeeded":0,"errored":0,"canceled":0,"expired":0},"ended_at":null,"created_at":"2024-10-15T12:00:00Z","expires_at":"2024-10-16T12:00:00Z","cancel_initiated_at":null,"archived_at":null,"results_url":null}"#;
+        let batch: MessageBatch = serde_json::from_str(raw).unwrap();
+        assert_eq!(batch.id, "msgbatch_01");
+        assert_eq!(batch.processing_status, BatchStatus::InProgress);
+        assert_eq!(batch.request_counts.processing, 2);
+        assert_eq!(batch.results_url, None);
+    }
+
+    #[test]
+    fn should_decode_results_split_across_chunks() {
+        let body = concat!(
+            r#"{"custom_id":"first","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"Invalid"}}}}"#,
+            "\n",
+            r#"{"custom_id":"second","result":{"type":"expired"}}"#,
+            "\n",
+            r#"{"custom_id":"third","result":{"type":"canceled"}}"#,
+        );
+
+        let mut decoder = JsonLinesDecoder::<BatchResult>::new();
+        let mut results = Vec::new();
+        for chunk in body.as_bytes().chunks(7) {
+            results.extend(decoder.decode(chunk).into_iter().map(Result::unwrap));
+        }
+        results.extend(decoder.finish().into_iter().map(Result::unwrap));
+
+        let ids: Vec<&str> = results
+            .iter()
+            .map(|result| result.custom_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["first", "second", "third"]);
+        assert!(matches!(
+            &results[0].result,
+            BatchOutcome::Errored { error } if error.error.error_type == ApiErrorType::InvalidRequest
+        ));
+        assert_eq!(results[1].result, BatchOutcome::Expired);
+        assert_eq!(results[2].result, BatchOutcome::Canceled);
+    }
+}
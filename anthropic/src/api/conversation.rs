@@ -0,0 +1,322 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::message::{Content, Message, Role};
+
+/// An ordered list of messages exchanged with the model.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Conversation {
+    pub messages: Vec<Message>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    pub fn into_messages(self) -> Vec<Message> {
+        self.messages
+    }
+
+    /// Merges consecutive messages sharing the same role by concatenating their content blocks.
+    ///
+    /// The API requires user and assistant turns to alternate, which this restores for
+    /// conversations assembled from several sources.
+    pub fn normalize(&mut self) {
+        let mut merged: Vec<Message> = Vec::with_capacity(self.messages.len());
+        for message in self.messages.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.role == message.role => last.content.extend(message.content),
+                _ => merged.push(message),
+            }
+        }
+        self.messages = merged;
+    }
+
+    /// Removes thinking content from assistant turns, e.g. before persisting the conversation.
+    ///
+    /// With `keep_signatures`, thinking blocks are kept with their text emptied so their
+    /// signature can still be sent back to continue the conversation, and redacted thinking
+    /// blocks, whose data is only meaningful to the API, are kept as is. Otherwise both kinds of
+    /// blocks are removed.
+    pub fn strip_thinking(&mut self, keep_signatures: bool) {
+        for message in self
+            .messages
+            .iter_mut()
+            .filter(|message| message.role == Role::Assistant)
+        {
+            if keep_signatures {
+                for content in message.content.iter_mut() {
+                    if let Content::Thinking { thinking, .. } = content {
+                        thinking.clear();
+                    }
+                }
+            } else {
+                message.content.retain(|content| {
+                    !matches!(
+                        content,
+                        Content::Thinking { .. } | Content::RedactedThinking { .. }
+                    )
+                });
+            }
+        }
+    }
+
+    /// Renders the conversation as a readable transcript, e.g. for logs or test output.
+    ///
+    /// Each message is rendered on its own line, prefixed by its role. Non-text blocks are
+    /// summarized, such as `[image]` or `[tool_use: name]`.
+    pub fn transcript(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, message) in self.messages.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let role = match message.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            write!(f, "{role}:")?;
+            for content in &message.content {
+                match content {
+                    Content::Text { text, .. } => write!(f, " {text}")?,
+                    Content::Image { .. } => write!(f, " [image]")?,
+                    Content::Document { title, .. } => match title {
+                        Some(title) => write!(f, " [document: {title}]")?,
+                        None => write!(f, " [document]")?,
+                    },
+                    Content::Thinking { .. } => write!(f, " [thinking]")?,
+                    Content::RedactedThinking { .. } => write!(f, " [redacted_thinking]")?,
+                    Content::ToolUse { name, .. } => write!(f, " [tool_use: {name}]")?,
+                    Content::ToolResult { tool_use_id, .. } => {
+                        write!(f, " [tool_result: {tool_use_id}]")?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Extend<Message> for Conversation {
+    /// Appends messages in order, without merging same-role messages.
+    ///
+    /// Call [`Conversation::normalize`] afterwards to restore alternating roles.
+    fn extend<T: IntoIterator<Item = Message>>(&mut self, messages: T) {
+        self.messages.extend(messages);
+    }
+}
+
+impl From<Vec<Message>> for Conversation {
+    fn from(messages: Vec<Message>) -> Self {
+        Self { messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::message::ImageSource;
+    use pretty_assertions::assert_eq;
+
+    fn message(role: Role, text: &str) -> Message {
+        Message {
+            role,
+            content: vec![Content::Text {
+                text: text.to_string(),
+                cache_control: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn should_merge_consecutive_user_messages() {
+        let mut conversation = Conversation::from(vec![
+            message(Role::User, "first"),
+            message(Role::User, "second"),
+            message(Role::User, "third"),
+            message(Role::Assistant, "reply"),
+            message(Role::User, "follow-up"),
+        ]);
+        conversation.normalize();
+
+        assert_eq!(
+            conversation.messages,
+            vec![
+                Message {
+                    role: Role::User,
+                    content: vec![
+                        Content::Text {
+                            text: "first".to_string(),
+                            cache_control: None,
+                        },
+                        Content::Text {
+                            text: "second".to_string(),
+                            cache_control: None,
+                        },
+                        Content::Text {
+                            text: "third".to_string(),
+                            cache_control: None,
+                        },
+                    ],
+                },
+                message(Role::Assistant, "reply"),
+                message(Role::User, "follow-up"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_extend_conversation_in_order() {
+        let mut conversation = Conversation::from(vec![message(Role::User, "hello")]);
+        conversation.extend(vec![
+            message(Role::Assistant, "hi"),
+            message(Role::User, "how are you?"),
+            message(Role::User, "still there?"),
+        ]);
+
+        assert_eq!(conversation.messages.len(), 4);
+        assert_eq!(
+            conversation.messages,
+            vec![
+                message(Role::User, "hello"),
+                message(Role::Assistant, "hi"),
+                message(Role::User, "how are you?"),
+                message(Role::User, "still there?"),
+            ]
+        );
+
+        conversation.normalize();
+        assert_eq!(conversation.messages.len(), 3);
+    }
+
+    fn thinking_conversation() -> Conversation {
+        Conversation::from(vec![
+            message(Role::User, "What is 2 + 2?"),
+            Message {
+                role: Role::Assistant,
+                content: vec![
+                    Content::Thinking {
+                        thinking: "Adding two and two".to_string(),
+                        signature: "signature".to_string(),
+                    },
+                    Content::RedactedThinking {
+                        data: "encrypted".to_string(),
+                    },
+                    Content::Text {
+                        text: "4".to_string(),
+                        cache_control: None,
+                    },
+                ],
+            },
+        ])
+    }
+
+    #[test]
+    fn should_strip_thinking_keeping_signatures() {
+        let mut conversation = thinking_conversation();
+        conversation.strip_thinking(true);
+
+        assert_eq!(
+            conversation.messages[1].content,
+            vec![
+                Content::Thinking {
+                    thinking: String::new(),
+                    signature: "signature".to_string(),
+                },
+                Content::RedactedThinking {
+                    data: "encrypted".to_string(),
+                },
+                Content::Text {
+                    text: "4".to_string(),
+                    cache_control: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_strip_thinking_blocks() {
+        let mut conversation = thinking_conversation();
+        conversation.strip_thinking(false);
+
+        assert_eq!(
+            conversation.messages[0],
+            message(Role::User, "What is 2 + 2?")
+        );
+        assert_eq!(conversation.messages[1], message(Role::Assistant, "4"));
+    }
+
+    #[test]
+    fn should_keep_alternating_messages_untouched() {
+        let messages = vec![message(Role::User, "hello"), message(Role::Assistant, "hi")];
+        let mut conversation = Conversation::from(messages.clone());
+        conversation.normalize();
+        assert_eq!(conversation.messages, messages);
+    }
+
+    #[test]
+    fn should_render_transcript() {
+        let conversation = Conversation::from(vec![
+            Message {
+                role: Role::User,
+                content: vec![
+                    Content::Text {
+                        text: "What is in this picture?".to_string(),
+                        cache_control: None,
+                    },
+                    Content::Image {
+                        source: ImageSource::Url {
+                            url: "https://example.com/cat.png".to_string(),
+                        },
+                        cache_control: None,
+                    },
+                ],
+            },
+            Message {
+                role: Role::Assistant,
+                content: vec![
+                    Content::Text {
+                        text: "Let me look it up.".to_string(),
+                        cache_control: None,
+                    },
+                    Content::ToolUse {
+                        id: "toolu_01".to_string(),
+                        name: "image_search".to_string(),
+                        input: serde_json::json!({ "query": "cat" }),
+                        cache_control: None,
+                    },
+                ],
+            },
+            Message {
+                role: Role::User,
+                content: vec![Content::ToolResult {
+                    tool_use_id: "toolu_01".to_string(),
+                    content: Vec::new(),
+                    is_error: None,
+                    cache_control: None,
+                }],
+            },
+            message(Role::Assistant, "It is a cat."),
+        ]);
+
+        assert_eq!(
+            conversation.transcript(),
+            "User: What is in this picture? [image]\n\
+             Assistant: Let me look it up. [tool_use: image_search]\n\
+             User: [tool_result: toolu_01]\n\
+             Assistant: It is a cat."
+        );
+    }
+}
@@ -1,8 +1,14 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use core::fmt;
-use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, path::Path, str::FromStr};
 
-use crate::models::model::Model;
+use super::{
+    stream::MessageStream,
+    tool::{Tool, ToolChoice},
+};
+use crate::{client::Client, error::AnthropicError, models::model::Model};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Message {
@@ -10,6 +16,61 @@ pub struct Message {
     pub content: Vec<Content>,
 }
 
+impl Message {
+    /// A user message with a single text block.
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: vec![Content::text(text)],
+        }
+    }
+
+    /// An assistant message with a single text block, e.g. to prefill the response.
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: vec![Content::text(text)],
+        }
+    }
+
+    /// Wraps this message into a single-turn request.
+    pub fn into_request(self, model: Model, max_tokens: u32) -> MessageRequest {
+        MessageRequest::new(model, max_tokens, vec![self])
+    }
+
+    /// Builds the user message answering tool calls, with one `tool_result` block per
+    /// `(tool_use_id, value)` pair, in order.
+    ///
+    /// String values are sent as text, other values as their JSON serialization.
+    pub fn tool_results(results: Vec<(String, serde_json::Value)>) -> Result<Self, AnthropicError> {
+        let content = results
+            .into_iter()
+            .map(|(tool_use_id, value)| {
+                if tool_use_id.is_empty() {
+                    return Err(AnthropicError::InvalidParameter {
+                        field: "tool_use_id",
+                        message: "must not be empty".to_string(),
+                    });
+                }
+                let text = match value {
+                    serde_json::Value::String(text) => text,
+                    value => value.to_string(),
+                };
+                Ok(Content::ToolResult {
+                    tool_use_id,
+                    content: vec![Content::text(text)],
+                    is_error: None,
+                    cache_control: None,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            role: Role::User,
+            content,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
@@ -18,17 +79,355 @@ pub enum Role {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct Content {
-    pub text: String,
-    #[serde(rename = "type")]
-    pub content_type: ContentType,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Content {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Image {
+        source: ImageSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    /// A document such as a PDF, whose text and page images are read by the model.
+    Document {
+        source: DocumentSource,
+        /// Title of the document, which the model may use when citing it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Context about the document that is not passed to the model as document content.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        context: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Thinking {
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    ToolResult {
+        tool_use_id: String,
+        #[serde(default, deserialize_with = "deserialize_tool_result_content")]
+        content: Vec<Content>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+/// Marks the prompt up to and including a block for caching, see [`Content::with_cache_control`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheControl {
+    /// Cached for 5 minutes, refreshed every time the cache is read.
+    Ephemeral,
+}
+
+/// Accepts both the string shorthand and the list of blocks allowed for tool result content.
+fn deserialize_tool_result_content<'de, D>(deserializer: D) -> Result<Vec<Content>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ToolResultContent {
+        Text(String),
+        Blocks(Vec<Content>),
+    }
+
+    Ok(match ToolResultContent::deserialize(deserializer)? {
+        ToolResultContent::Text(text) => vec![Content::text(text)],
+        ToolResultContent::Blocks(blocks) => blocks,
+    })
+}
+
+impl Content {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    /// Marks the prompt up to and including this block for caching.
+    ///
+    /// Thinking blocks cannot be cached and are returned unchanged.
+    pub fn with_cache_control(mut self, control: CacheControl) -> Self {
+        match &mut self {
+            Self::Text { cache_control, .. }
+            | Self::Image { cache_control, .. }
+            | Self::Document { cache_control, .. }
+            | Self::ToolUse { cache_control, .. }
+            | Self::ToolResult { cache_control, .. } => *cache_control = Some(control),
+            Self::Thinking { .. } | Self::RedactedThinking { .. } => {}
+        }
+        self
+    }
+
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        match self {
+            Self::Text { cache_control, .. }
+            | Self::Image { cache_control, .. }
+            | Self::Document { cache_control, .. }
+            | Self::ToolUse { cache_control, .. }
+            | Self::ToolResult { cache_control, .. } => *cache_control,
+            Self::Thinking { .. } | Self::RedactedThinking { .. } => None,
+        }
+    }
+
+    /// PDF document block from base64 encoded `data`.
+    pub fn pdf_base64(data: impl Into<String>) -> Self {
+        Self::document(DocumentSource::Base64 {
+            media_type: DocumentMediaType::Pdf,
+            data: data.into(),
+        })
+    }
+
+    /// PDF document block downloaded by the API from `url`.
+    pub fn pdf_url(url: impl Into<String>) -> Self {
+        Self::document(DocumentSource::Url { url: url.into() })
+    }
+
+    fn document(source: DocumentSource) -> Self {
+        Self::Document {
+            source,
+            title: None,
+            context: None,
+            cache_control: None,
+        }
+    }
+
+    /// Sets the title of a document block, other blocks are returned unchanged.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        if let Self::Document { title: current, .. } = &mut self {
+            *current = Some(title.into());
+        }
+        self
+    }
+
+    /// Sets the context of a document block, other blocks are returned unchanged.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        if let Self::Document {
+            context: current, ..
+        } = &mut self
+        {
+            *current = Some(context.into());
+        }
+        self
+    }
+
+    /// Mutable access to the text of a text block, e.g. for redaction before persistence.
+    ///
+    /// Returns `None` for non-text blocks.
+    pub fn as_text_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Text { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Downloads the image at `url` and embeds it as a base64 image block.
+    ///
+    /// Useful for backends that don't accept URL image sources. The media type is taken from the
+    /// `content-type` header and the download is bounded by [`Config::max_image_size`].
+    ///
+    /// [`Config::max_image_size`]: crate::config::Config::max_image_size
+    pub async fn image_fetch(url: &str, client: &Client) -> Result<Self, AnthropicError> {
+        let mut response = client
+            .http_client()
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let media_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim)
+            .unwrap_or_default()
+            .parse::<ImageMediaType>()?;
+
+        let limit = client.max_image_size();
+        if response
+            .content_length()
+            .is_some_and(|length| length > limit as u64)
+        {
+            return Err(AnthropicError::ImageTooLarge(limit));
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() + chunk.len() > limit {
+                return Err(AnthropicError::ImageTooLarge(limit));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(Self::Image {
+            source: ImageSource::Base64 {
+                media_type,
+                data: STANDARD.encode(data),
+            },
+            cache_control: None,
+        })
+    }
+}
+
+/// Builds an ordered list of content blocks, e.g. text interleaved with images.
+#[derive(Debug, Clone, Default)]
+pub struct ContentBuilder {
+    content: Vec<Content>,
+}
+
+impl ContentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.content.push(Content::text(text));
+        self
+    }
+
+    pub fn image_base64(mut self, media_type: ImageMediaType, data: impl Into<String>) -> Self {
+        self.content.push(Content::Image {
+            source: ImageSource::Base64 {
+                media_type,
+                data: data.into(),
+            },
+            cache_control: None,
+        });
+        self
+    }
+
+    pub fn image_url(mut self, url: impl Into<String>) -> Self {
+        self.content.push(Content::Image {
+            source: ImageSource::Url { url: url.into() },
+            cache_control: None,
+        });
+        self
+    }
+
+    pub fn pdf_base64(mut self, data: impl Into<String>) -> Self {
+        self.content.push(Content::pdf_base64(data));
+        self
+    }
+
+    pub fn build(self) -> Vec<Content> {
+        self.content
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 {
+        media_type: ImageMediaType,
+        data: String,
+    },
+    Url {
+        url: String,
+    },
+}
+
+/// Source of a [`Content::Document`] block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    Base64 {
+        media_type: DocumentMediaType,
+        data: String,
+    },
+    Url {
+        url: String,
+    },
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DocumentMediaType {
+    #[serde(rename = "application/pdf")]
+    Pdf,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ImageMediaType {
+    #[serde(rename = "image/jpeg")]
+    Jpeg,
+    #[serde(rename = "image/png")]
+    Png,
+    #[serde(rename = "image/gif")]
+    Gif,
+    #[serde(rename = "image/webp")]
+    Webp,
+}
+
+impl ImageMediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+impl FromStr for ImageMediaType {
+    type Err = AnthropicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "image/jpeg" => Ok(Self::Jpeg),
+            "image/png" => Ok(Self::Png),
+            "image/gif" => Ok(Self::Gif),
+            "image/webp" => Ok(Self::Webp),
+            _ => Err(AnthropicError::UnsupportedMediaType(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ImageMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Beta enabling Claude 3.5 Sonnet's 8192 output token limit.
+const MAX_TOKENS_3_5_SONNET_BETA: &str = "max-tokens-3-5-sonnet-2024-07-15";
+/// Beta enabling PDF document blocks.
+const PDFS_BETA: &str = "pdfs-2024-09-25";
+/// Beta enabling `cache_control` on content blocks.
+const PROMPT_CACHING_BETA: &str = "prompt-caching-2024-07-31";
+/// Smallest thinking budget accepted by the API.
+const MIN_THINKING_BUDGET_TOKENS: u32 = 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageRequest {
     /// The model that will complete your prompt e.g. Claude 3.5 Sonnet
     pub model: Model,
 
+    /// Beta features explicitly enabled for this request, sent in the `anthropic-beta` header.
+    ///
+    /// Betas required by the request contents are added automatically, see
+    /// [`MessageRequest::required_betas`].
+    #[serde(skip)]
+    pub betas: Vec<String>,
+
     /// The maximum number of tokens to generate before stopping.
     ///
     /// Defaults to 1000 tokens.
@@ -52,7 +451,7 @@ pub struct MessageRequest {
     ///
     /// A system prompt is a way of providing context and instructions to Claude, such as specifying a particular goal or role.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub system: Option<String>,
+    pub system: Option<SystemPrompt>,
 
     /// Amount of randomness injected into the response.
     ///
@@ -60,20 +459,139 @@ pub struct MessageRequest {
     ///
     /// Use temperature closer to 0.0 for analytical / multiple choice, and closer to 1.0 for creative and generative tasks.
     /// Note that even with temperature of 0.0, the results will not be fully deterministic.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_finite"
+    )]
+    pub temperature: Option<f64>,
+
+    /// Extended thinking, letting the model reason in `thinking` blocks before answering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+
+    /// How the model should use the provided tools, left to the model by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Tools the model may use, see [`Tool`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f32>,
+    pub tools: Option<Vec<Tool>>,
 
     /// Only sample from the top K options for each subsequent token.
     ///
     /// Used to remove "long tail" low probability responses. Learn more technical details here.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub top_k: Option<i8>,
+    pub top_k: Option<u32>,
 
     /// Use nucleus sampling.
     ///
     /// In nucleus sampling, we compute the cumulative distribution over all the options for each subsequent token in decreasing probability order and cut it off once it reaches a particular probability specified by top_p. You should either alter temperature or top_p, but not both.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub top_p: Option<i8>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_finite"
+    )]
+    pub top_p: Option<f64>,
+}
+
+/// Extended thinking setting of a request, see [`MessageRequest::with_thinking`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ThinkingConfig {
+    /// Tokens of `max_tokens` the model may spend on thinking, at least 1024.
+    Enabled {
+        budget_tokens: u32,
+    },
+    Disabled,
+}
+
+/// System prompt of a request, either plain text or a list of text blocks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<SystemBlock>),
+}
+
+impl From<String> for SystemPrompt {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for SystemPrompt {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<SystemBlock>> for SystemPrompt {
+    fn from(blocks: Vec<SystemBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+/// A text block of a [`SystemPrompt`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename = "text")]
+pub struct SystemBlock {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl SystemBlock {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    /// Marks the prompt up to and including this block for caching.
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+}
+
+/// Upper bound of the tokens of an image, reached by images of about 1.15 megapixels.
+const IMAGE_TOKENS: usize = 1600;
+/// Rough cost of a document, counted as a single page of text and its image.
+const DOCUMENT_TOKENS: usize = 3000;
+
+/// Characters of text and tokens of images of `content`, see [`MessageRequest::estimate_input_tokens`].
+fn estimate_content(content: &[Content]) -> (usize, usize) {
+    content
+        .iter()
+        .fold((0, 0), |(chars, tokens), content| match content {
+            Content::Text { text, .. } => (chars + text.chars().count(), tokens),
+            Content::Image { .. } => (chars, tokens + IMAGE_TOKENS),
+            Content::Document { .. } => (chars, tokens + DOCUMENT_TOKENS),
+            Content::Thinking { thinking, .. } => (chars + thinking.chars().count(), tokens),
+            Content::RedactedThinking { data } => (chars + data.chars().count(), tokens),
+            Content::ToolUse { name, input, .. } => (
+                chars + name.chars().count() + input.to_string().chars().count(),
+                tokens,
+            ),
+            Content::ToolResult { content, .. } => {
+                let (result_chars, result_tokens) = estimate_content(content);
+                (chars + result_chars, tokens + result_tokens)
+            }
+        })
+}
+
+/// `serde_json` silently writes non-finite floats as `null`, which the API would reject with an
+/// unhelpful message, so they fail serialization instead.
+fn serialize_finite<S>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) if !value.is_finite() => Err(serde::ser::Error::custom(format!(
+            "{value} is not a finite number"
+        ))),
+        value => value.serialize(serializer),
+    }
 }
 
 impl MessageRequest {
@@ -86,6 +604,14 @@ impl MessageRequest {
         }
     }
 
+    /// Sets the maximum number of tokens to generate, which must be greater than 0.
+    ///
+    /// The value is checked by [`MessageRequest::validate`].
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
     pub fn with_metadata(mut self, metadata: MessageMetadata) -> Self {
         self.metadata = Some(metadata);
         self
@@ -102,30 +628,255 @@ impl MessageRequest {
     }
 
     pub fn with_system(mut self, system: impl Into<String>) -> Self {
-        self.system = Some(system.into());
+        self.system = Some(SystemPrompt::Text(system.into()));
         self
     }
 
-    pub fn with_temperature(mut self, temperature: f32) -> Self {
+    /// Sets the system prompt as a list of blocks, e.g. to cache a large static prefix while
+    /// leaving a dynamic suffix uncached.
+    pub fn with_system_blocks(mut self, blocks: Vec<SystemBlock>) -> Self {
+        self.system = Some(SystemPrompt::Blocks(blocks));
+        self
+    }
+
+    /// Reads the system prompt from a file.
+    pub fn with_system_from_path(self, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let system = std::fs::read_to_string(path)?;
+        Ok(self.with_system(system))
+    }
+
+    /// Enables extended thinking with a budget of `budget_tokens` reasoning tokens.
+    ///
+    /// The budget counts towards `max_tokens`, which must be greater.
+    pub fn with_thinking(mut self, budget_tokens: u32) -> Self {
+        self.thinking = Some(ThinkingConfig::Enabled { budget_tokens });
+        self
+    }
+
+    /// Prefer [`MessageRequest::with_sampling`], which can't combine `temperature` and `top_p`.
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
         self.temperature = Some(temperature);
         self
     }
 
-    pub fn with_top_k(mut self, top_k: i8) -> Self {
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Controls whether and which tools the model must use, see [`ToolChoice`].
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Prefer [`MessageRequest::with_sampling`], which can't combine `temperature` and `top_p`.
+    pub fn with_top_k(mut self, top_k: u32) -> Self {
         self.top_k = Some(top_k);
         self
     }
 
-    pub fn with_top_p(mut self, top_p: i8) -> Self {
+    /// Prefer [`MessageRequest::with_sampling`], which can't combine `temperature` and `top_p`.
+    pub fn with_top_p(mut self, top_p: f64) -> Self {
         self.top_p = Some(top_p);
         self
     }
+
+    /// Enables a beta feature for this request.
+    pub fn with_beta(mut self, beta: impl Into<String>) -> Self {
+        self.betas.push(beta.into());
+        self
+    }
+
+    /// Beta features to send for this request: the explicit [`MessageRequest::betas`] followed by
+    /// the betas required by the features the request uses, without duplicates.
+    ///
+    /// Betas are only derived when needed, so requests not using beta features send none.
+    pub fn required_betas(&self) -> Vec<String> {
+        let mut betas = self.betas.clone();
+        if self.model == Model::Claude35Sonnet && self.max_tokens > 4096 {
+            betas.push(MAX_TOKENS_3_5_SONNET_BETA.to_string());
+        }
+        if self.uses_cache_control() {
+            betas.push(PROMPT_CACHING_BETA.to_string());
+        }
+        if self.uses_documents() {
+            betas.push(PDFS_BETA.to_string());
+        }
+        let mut seen = std::collections::HashSet::new();
+        betas.retain(|beta| seen.insert(beta.clone()));
+        betas
+    }
+
+    fn uses_documents(&self) -> bool {
+        self.messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .any(|content| matches!(content, Content::Document { .. }))
+    }
+
+    fn uses_cache_control(&self) -> bool {
+        let system = match &self.system {
+            Some(SystemPrompt::Blocks(blocks)) => blocks.as_slice(),
+            _ => &[],
+        };
+        system.iter().any(|block| block.cache_control.is_some())
+            || self
+                .messages
+                .iter()
+                .flat_map(|message| &message.content)
+                .any(|content| content.cache_control().is_some())
+    }
+
+    /// Rough estimate of the input tokens of the request, assuming about 4 characters per token
+    /// and a fixed cost per image.
+    ///
+    /// Meant for budgeting before sending without a round trip, [`Client::count_tokens`] returns
+    /// the exact count.
+    pub fn estimate_input_tokens(&self) -> u32 {
+        let mut chars = match &self.system {
+            Some(SystemPrompt::Text(text)) => text.chars().count(),
+            Some(SystemPrompt::Blocks(blocks)) => {
+                blocks.iter().map(|block| block.text.chars().count()).sum()
+            }
+            None => 0,
+        };
+        let mut tokens = 0;
+        for tool in self.tools.iter().flatten() {
+            chars += serde_json::to_string(tool).map_or(0, |tool| tool.chars().count());
+        }
+        for message in &self.messages {
+            let (message_chars, message_tokens) = estimate_content(&message.content);
+            chars += message_chars;
+            tokens += message_tokens;
+        }
+        let tokens = tokens + chars.div_ceil(4);
+        u32::try_from(tokens).unwrap_or(u32::MAX)
+    }
+
+    /// Sends the request with `client`, allowing requests to be built and sent in one chain.
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), anthropic_rs::error::AnthropicError> {
+    /// use anthropic_rs::prelude::*;
+    ///
+    /// let client = Client::new(Config::from_env()?)?;
+    /// let response = MessageRequest::new(Model::Claude35Sonnet, 1024, Vec::new())
+    ///     .with_system("Answer in one sentence")
+    ///     .send(&client)
+    ///     .await?;
+    /// println!("{}", response.text());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send(self, client: &Client) -> Result<MessageResponse, AnthropicError> {
+        client.create_message(self).await
+    }
+
+    /// Streams the response with `client`, enabling `stream` on the request.
+    pub async fn send_stream(self, client: &Client) -> Result<MessageStream, AnthropicError> {
+        client.stream_message(self.with_stream(true)).await
+    }
+
+    /// Checks the request for values the API would reject.
+    ///
+    /// Called by the client before sending, so invalid requests fail without a round trip.
+    pub fn validate(&self) -> Result<(), AnthropicError> {
+        if self.max_tokens == 0 {
+            return Err(AnthropicError::InvalidParameter {
+                field: "max_tokens",
+                message: "must be greater than 0".to_string(),
+            });
+        }
+        for (field, value) in [("temperature", self.temperature), ("top_p", self.top_p)] {
+            if let Some(value) = value {
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(AnthropicError::InvalidParameter {
+                        field,
+                        message: format!("must be between 0.0 and 1.0, got {value}"),
+                    });
+                }
+            }
+        }
+        if let Some(ThinkingConfig::Enabled { budget_tokens }) = self.thinking {
+            if budget_tokens < MIN_THINKING_BUDGET_TOKENS || budget_tokens >= self.max_tokens {
+                return Err(AnthropicError::InvalidParameter {
+                    field: "thinking",
+                    message: format!(
+                        "budget_tokens must be at least {MIN_THINKING_BUDGET_TOKENS} and less than max_tokens, got {budget_tokens}"
+                    ),
+                });
+            }
+        }
+        if let Some(tool_choice) = &self.tool_choice {
+            let tools = self.tools.as_deref().unwrap_or_default();
+            let valid = match tool_choice {
+                ToolChoice::Auto | ToolChoice::None => true,
+                ToolChoice::Any => !tools.is_empty(),
+                ToolChoice::Tool { name } => tools.iter().any(|tool| tool.name == *name),
+            };
+            if !valid {
+                return Err(AnthropicError::InvalidParameter {
+                    field: "tool_choice",
+                    message: format!("{tool_choice:?} does not match the provided tools"),
+                });
+            }
+        }
+        if let Some(first) = self.messages.first() {
+            if first.role != Role::User {
+                return Err(AnthropicError::InvalidParameter {
+                    field: "messages",
+                    message: "the first message must have the user role".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the sampling strategy, replacing any previously set `temperature`, `top_k` or `top_p`.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.temperature = None;
+        self.top_k = None;
+        self.top_p = None;
+        match sampling {
+            Sampling::Temperature(temperature) => self.temperature = Some(temperature),
+            Sampling::TopP(top_p) => self.top_p = Some(top_p),
+            Sampling::TopK(top_k) => self.top_k = Some(top_k),
+            Sampling::TemperatureTopK { temperature, top_k } => {
+                self.temperature = Some(temperature);
+                self.top_k = Some(top_k);
+            }
+        }
+        self
+    }
+}
+
+/// Sampling strategy for a request.
+///
+/// Anthropic recommends altering either `temperature` or `top_p` but not both, so no variant
+/// combines them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    Temperature(f64),
+    TopP(f64),
+    TopK(u32),
+    TemperatureTopK { temperature: f64, top_k: u32 },
+}
+
+impl From<Message> for MessageRequest {
+    fn from(message: Message) -> Self {
+        Self {
+            messages: vec![message],
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for MessageRequest {
     fn default() -> Self {
         Self {
-            model: Model::Claude35Sonnet,
+            model: Model::default(),
+            betas: Vec::new(),
             max_tokens: 1000,
             messages: Vec::new(),
             metadata: None,
@@ -133,6 +884,9 @@ impl Default for MessageRequest {
             stream: false,
             system: None,
             temperature: None,
+            thinking: None,
+            tool_choice: None,
+            tools: None,
             top_k: None,
             top_p: None,
         }
@@ -150,13 +904,93 @@ pub struct MessageResponse {
     #[serde(rename = "type")]
     pub message_type: MessageType,
     pub role: RoleResponse,
+    /// May be empty, e.g. when the model stops right away, so prefer the accessors over indexing.
+    #[serde(default)]
     pub content: Vec<Content>,
     pub model: Model,
     pub stop_reason: Option<StopReason>,
     pub stop_sequence: Option<String>,
+    /// Zeroed when the response has no usage, as with some compatibility gateways.
+    #[serde(default)]
     pub usage: TokenUsage,
 }
 
+impl MessageResponse {
+    /// Concatenates the text of all text content blocks.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|content| match content {
+                Content::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Text of the first text content block, skipping blocks of other types.
+    pub fn first_text(&self) -> Option<&str> {
+        self.content.iter().find_map(|content| match content {
+            Content::Text { text, .. } => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// First content block, if any.
+    pub fn first_content(&self) -> Option<&Content> {
+        self.content.first()
+    }
+
+    /// Last content block, if any, e.g. the tool use the model stopped on.
+    pub fn last_content(&self) -> Option<&Content> {
+        self.content.last()
+    }
+
+    /// Returns the concatenated text, failing if the model stopped to call a tool.
+    ///
+    /// Meant for flows that never expect tool calls, where one indicates a prompt bug.
+    pub fn expect_text(self) -> Result<String, AnthropicError> {
+        if self.stop_reason == Some(StopReason::ToolUse) {
+            return Err(AnthropicError::UnexpectedToolUse);
+        }
+        Ok(self.text())
+    }
+
+    /// Builds a minimal response holding a single text block, for use as a test fixture.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn mock_text(text: impl Into<String>) -> Self {
+        Self::from(vec![Content::text(text)])
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl From<Vec<Content>> for MessageResponse {
+    fn from(content: Vec<Content>) -> Self {
+        Self {
+            id: "msg_mock".to_string(),
+            message_type: MessageType::Message,
+            role: RoleResponse::Assistant,
+            content,
+            model: Model::default(),
+            stop_reason: Some(StopReason::EndTurn),
+            stop_sequence: None,
+            usage: TokenUsage {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_input_tokens: None,
+                cache_read_input_tokens: None,
+                server_tool_use: None,
+            },
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl From<String> for MessageResponse {
+    fn from(text: String) -> Self {
+        Self::mock_text(text)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum MessageType {
@@ -203,23 +1037,253 @@ impl fmt::Display for StopReason {
     }
 }
 
+/// Input tokens of a request, returned by [`Client::count_tokens`].
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum ContentType {
-    Text,
+pub struct TokenCount {
+    pub input_tokens: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct TokenUsage {
-    pub input_tokens: u32,
-    pub output_tokens: u32,
+/// Body of a token counting request, which only accepts the fields contributing to the input.
+#[derive(Debug, Serialize)]
+pub(crate) struct CountTokensRequest<'a> {
+    pub model: &'a Model,
+    pub messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<&'a SystemPrompt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<&'a ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<&'a Vec<Tool>>,
 }
 
-#[cfg(test)]
+impl<'a> From<&'a MessageRequest> for CountTokensRequest<'a> {
+    fn from(request: &'a MessageRequest) -> Self {
+        Self {
+            model: &request.model,
+            messages: &request.messages,
+            system: request.system.as_ref(),
+            thinking: request.thinking,
+            tool_choice: request.tool_choice.as_ref(),
+            tools: request.tools.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    /// Input tokens written to the prompt cache, billed at a higher rate than `input_tokens`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+    /// Input tokens read from the prompt cache, billed at a lower rate than `input_tokens`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+    /// Requests made by server tools, billed separately from tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tool_use: Option<ServerToolUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerToolUsage {
+    #[serde(default)]
+    pub web_search_requests: u32,
+}
+
+impl TokenUsage {
+    /// Signed difference between `other` and `self`, positive when `other` used more tokens.
+    pub fn diff(&self, other: &TokenUsage) -> TokenUsageDiff {
+        TokenUsageDiff {
+            input_tokens: i64::from(other.input_tokens) - i64::from(self.input_tokens),
+            output_tokens: i64::from(other.output_tokens) - i64::from(self.output_tokens),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenUsageDiff {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+impl fmt::Display for TokenUsageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input tokens: {:+}, output tokens: {:+}",
+            self.input_tokens, self.output_tokens
+        )
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn should_set_max_tokens() {
+        let request = MessageRequest::default();
+        assert_eq!(request.max_tokens, 1000);
+
+        let request = request.with_max_tokens(2048);
+        assert_eq!(request.max_tokens, 2048);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn should_build_text_messages() {
+        assert_eq!(
+            Message::user("Hello"),
+            Message {
+                role: Role::User,
+                content: vec![Content::Text {
+                    text: "Hello".to_string(),
+                    cache_control: None,
+                }],
+            }
+        );
+        assert_eq!(
+            Message::assistant("Hi"),
+            Message {
+                role: Role::Assistant,
+                content: vec![Content::text("Hi")],
+            }
+        );
+    }
+
+    #[test]
+    fn should_reject_zero_max_tokens() {
+        let request = MessageRequest::default().with_max_tokens(0);
+        assert!(matches!(
+            request.validate(),
+            Err(AnthropicError::InvalidParameter {
+                field: "max_tokens",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_out_of_range_sampling() {
+        for temperature in [-0.1, 1.5, f64::NAN] {
+            let request = MessageRequest::default().with_temperature(temperature);
+            assert!(matches!(
+                request.validate(),
+                Err(AnthropicError::InvalidParameter {
+                    field: "temperature",
+                    ..
+                })
+            ));
+        }
+
+        let request = MessageRequest::default().with_top_p(1.1);
+        assert!(matches!(
+            request.validate(),
+            Err(AnthropicError::InvalidParameter { field: "top_p", .. })
+        ));
+
+        let request = MessageRequest::default()
+            .with_temperature(0.0)
+            .with_top_p(1.0);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn should_require_conversation_to_start_with_user() {
+        let assistant = Message {
+            role: Role::Assistant,
+            content: vec![Content::Text {
+                text: "Hello, how can I help?".to_string(),
+                cache_control: None,
+            }],
+        };
+        let user = Message {
+            role: Role::User,
+            content: vec![Content::Text {
+                text: "Hi".to_string(),
+                cache_control: None,
+            }],
+        };
+
+        let request = MessageRequest::new(Model::Claude35Sonnet, 1024, vec![assistant.clone()]);
+        assert!(matches!(
+            request.validate(),
+            Err(AnthropicError::InvalidParameter {
+                field: "messages",
+                ..
+            })
+        ));
+
+        let request = MessageRequest::new(Model::Claude35Sonnet, 1024, vec![user, assistant]);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn should_derive_betas_from_request() {
+        let request = MessageRequest::new(Model::Claude35Sonnet, 4096, Vec::new());
+        assert!(request.required_betas().is_empty());
+
+        let request = request.with_max_tokens(8192);
+        assert_eq!(request.required_betas(), vec![MAX_TOKENS_3_5_SONNET_BETA]);
+
+        let request = request
+            .with_beta("custom-beta-2024-01-01")
+            .with_beta(MAX_TOKENS_3_5_SONNET_BETA);
+        assert_eq!(
+            request.required_betas(),
+            vec!["custom-beta-2024-01-01", MAX_TOKENS_3_5_SONNET_BETA]
+        );
+
+        let request = MessageRequest::new(Model::Claude3Haiku, 8192, Vec::new());
+        assert!(request.required_betas().is_empty());
+
+        let message = Message {
+            role: Role::User,
+            content: vec![ContentBuilder::new()
+                .text("Long document")
+                .build()
+                .remove(0)
+                .with_cache_control(CacheControl::Ephemeral)],
+        };
+        let request = MessageRequest::new(Model::Claude3Haiku, 1024, vec![message]);
+        assert_eq!(request.required_betas(), vec![PROMPT_CACHING_BETA]);
+    }
+
+    #[test]
+    fn should_serialize_cache_control() {
+        let content = ContentBuilder::new()
+            .text("Long document")
+            .build()
+            .remove(0);
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({ "type": "text", "text": "Long document" })
+        );
+
+        let content = content.with_cache_control(CacheControl::Ephemeral);
+        assert_eq!(content.cache_control(), Some(CacheControl::Ephemeral));
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "text",
+                "text": "Long document",
+                "cache_control": { "type": "ephemeral" },
+            })
+        );
+    }
+
+    #[test]
+    fn should_deserialize_cache_usage() {
+        let raw = r#"{"input_tokens":21,"output_tokens":393,"cache_creation_input_tokens":188086,"cache_read_input_tokens":0}"#;
+        let usage: TokenUsage = serde_json::from_str(raw).unwrap();
+        assert_eq!(usage.cache_creation_input_tokens, Some(188086));
+        assert_eq!(usage.cache_read_input_tokens, Some(0));
+    }
+
     #[test]
     fn should_set_metadata() {
         let request = MessageRequest::default();
@@ -242,6 +1306,49 @@ mod tests {
         assert_eq!(request.stop_sequences, Some(stop_sequences));
     }
 
+    #[test]
+    fn should_set_tools() {
+        let request = MessageRequest::default();
+        assert_eq!(request.tools, None);
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("tools")
+            .is_none());
+
+        let tools = vec![Tool::new(
+            "get_time",
+            serde_json::json!({ "type": "object" }),
+        )];
+        let request = request.with_tools(tools.clone());
+        assert_eq!(request.tools, Some(tools));
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["tools"],
+            serde_json::json!([{ "name": "get_time", "input_schema": { "type": "object" } }])
+        );
+    }
+
+    #[test]
+    fn should_estimate_input_tokens() {
+        let message = Message {
+            role: Role::User,
+            content: vec![
+                Content::Text {
+                    text: "a".repeat(398),
+                    cache_control: None,
+                },
+                Content::Image {
+                    source: ImageSource::Url {
+                        url: "https://example.com/cat.png".to_string(),
+                    },
+                    cache_control: None,
+                },
+            ],
+        };
+        let request =
+            MessageRequest::new(Model::default(), 1024, vec![message]).with_system("Be brief");
+        assert_eq!(request.estimate_input_tokens(), 1600 + 102);
+    }
+
     #[test]
     fn should_set_stream() {
         let request = MessageRequest::default();
@@ -261,7 +1368,59 @@ mod tests {
 
         let system = "You are an experienced software engineer";
         let request = request.with_system(system);
-        assert_eq!(request.system, Some(system.to_string()));
+        assert_eq!(request.system, Some(SystemPrompt::Text(system.to_string())));
+    }
+
+    #[test]
+    fn should_serialize_system_blocks() {
+        let request = MessageRequest::default().with_system("Be brief");
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["system"], serde_json::json!("Be brief"));
+
+        let request = MessageRequest::default().with_system_blocks(vec![
+            SystemBlock::new("Long static instructions")
+                .with_cache_control(CacheControl::Ephemeral),
+            SystemBlock::new("Today is Tuesday"),
+        ]);
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value["system"],
+            serde_json::json!([
+                {
+                    "type": "text",
+                    "text": "Long static instructions",
+                    "cache_control": { "type": "ephemeral" },
+                },
+                { "type": "text", "text": "Today is Tuesday" },
+            ])
+        );
+        assert_eq!(request.required_betas(), vec![PROMPT_CACHING_BETA]);
+
+        let request: MessageRequest = serde_json::from_value(value).unwrap();
+        assert!(matches!(request.system, Some(SystemPrompt::Blocks(blocks)) if blocks.len() == 2));
+    }
+
+    #[test]
+    fn should_set_system_from_path() {
+        let path = std::env::temp_dir().join("anthropic-rs-system-prompt.txt");
+        let system = "You are an experienced software engineer";
+        std::fs::write(&path, system).unwrap();
+
+        let request = MessageRequest::default()
+            .with_system_from_path(&path)
+            .unwrap();
+        assert_eq!(request.system, Some(SystemPrompt::from(system)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_fail_to_set_system_from_missing_path() {
+        let path = std::env::temp_dir().join("anthropic-rs-missing-system-prompt.txt");
+        let err = MessageRequest::default()
+            .with_system_from_path(&path)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
     }
 
     #[test]
@@ -269,7 +1428,7 @@ mod tests {
         let request = MessageRequest::default();
         assert_eq!(request.temperature, None);
 
-        let temperature: f32 = 0.9;
+        let temperature: f64 = 0.9;
         let request = request.with_temperature(temperature);
         assert_eq!(request.temperature, Some(temperature));
     }
@@ -279,7 +1438,7 @@ mod tests {
         let request = MessageRequest::default();
         assert_eq!(request.top_k, None);
 
-        let top_k: i8 = 1;
+        let top_k: u32 = 1;
         let request = request.with_top_k(top_k);
         assert_eq!(request.top_k, Some(top_k));
     }
@@ -289,18 +1448,231 @@ mod tests {
         let request = MessageRequest::default();
         assert_eq!(request.top_p, None);
 
-        let top_p: i8 = 1;
+        let top_p: f64 = 0.7;
         let request = request.with_top_p(top_p);
         assert_eq!(request.top_p, Some(top_p));
     }
 
+    #[test]
+    fn should_serialize_top_k_above_i8_range_and_fractional_top_p() {
+        let request = MessageRequest::default().with_top_k(500).with_top_p(0.9);
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["top_k"], serde_json::json!(500));
+        assert_eq!(value["top_p"], serde_json::json!(0.9));
+    }
+
+    #[test]
+    fn should_serialize_sampling_values_exactly() {
+        let request = MessageRequest::default()
+            .with_temperature(0.7)
+            .with_top_p(0.9);
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains(r#""temperature":0.7"#));
+        assert!(json.contains(r#""top_p":0.9"#));
+
+        let request: MessageRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(request.temperature, Some(0.7));
+    }
+
+    #[test]
+    fn should_serialize_sampling() {
+        let request = MessageRequest::default().with_sampling(Sampling::Temperature(0.5));
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["temperature"], serde_json::json!(0.5));
+        assert_eq!(value.get("top_k"), None);
+        assert_eq!(value.get("top_p"), None);
+
+        let request = MessageRequest::default().with_sampling(Sampling::TopP(0.5));
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value.get("temperature"), None);
+        assert_eq!(value.get("top_k"), None);
+        assert_eq!(value["top_p"], serde_json::json!(0.5));
+
+        let request = MessageRequest::default().with_sampling(Sampling::TopK(40));
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value.get("temperature"), None);
+        assert_eq!(value["top_k"], serde_json::json!(40));
+        assert_eq!(value.get("top_p"), None);
+
+        let request = MessageRequest::default().with_sampling(Sampling::TemperatureTopK {
+            temperature: 0.5,
+            top_k: 40,
+        });
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["temperature"], serde_json::json!(0.5));
+        assert_eq!(value["top_k"], serde_json::json!(40));
+        assert_eq!(value.get("top_p"), None);
+    }
+
+    #[test]
+    fn should_replace_previous_sampling() {
+        let request = MessageRequest::default()
+            .with_temperature(0.5)
+            .with_sampling(Sampling::TopP(0.9));
+        assert_eq!(request.temperature, None);
+        assert_eq!(request.top_p, Some(0.9));
+    }
+
+    #[test]
+    fn should_build_mock_response() {
+        let response = MessageResponse::mock_text("hello");
+        assert_eq!(response.text(), "hello");
+        assert_eq!(response.role, RoleResponse::Assistant);
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+
+        let response = MessageResponse::from("hello".to_string());
+        assert_eq!(response.text(), "hello");
+
+        let response = MessageResponse::from(vec![
+            Content::Text {
+                text: "hello ".to_string(),
+                cache_control: None,
+            },
+            Content::Image {
+                source: ImageSource::Url {
+                    url: "https://example.com/cat.png".to_string(),
+                },
+                cache_control: None,
+            },
+            Content::Text {
+                text: "world".to_string(),
+                cache_control: None,
+            },
+        ]);
+        assert_eq!(response.text(), "hello world");
+    }
+
+    #[test]
+    fn should_skip_tool_use_blocks_when_extracting_text() {
+        let response = MessageResponse::from(vec![
+            Content::ToolUse {
+                id: "toolu_01".to_string(),
+                name: "get_weather".to_string(),
+                input: serde_json::json!({ "location": "Paris" }),
+                cache_control: None,
+            },
+            Content::text("It is "),
+            Content::text("sunny"),
+        ]);
+        assert_eq!(response.text(), "It is sunny");
+        assert_eq!(response.first_text(), Some("It is "));
+
+        let response = MessageResponse::from(Vec::new());
+        assert_eq!(response.text(), "");
+        assert_eq!(response.first_text(), None);
+    }
+
+    #[test]
+    fn should_deserialize_response_without_usage() {
+        let raw = r#"{"id":"msg_01","type":"message","role":"assistant","content":[{"type":"text","text":"Hi"}],"model":"claude-3-haiku-20240307","stop_reason":"end_turn","stop_sequence":null}"#;
+        let response: MessageResponse = serde_json::from_str(raw).unwrap();
+        assert_eq!(response.text(), "Hi");
+        assert_eq!(response.usage, TokenUsage::default());
+        assert_eq!(response.usage.input_tokens, 0);
+        assert_eq!(response.usage.output_tokens, 0);
+    }
+
+    #[test]
+    fn should_deserialize_response_with_empty_content() {
+        let raw = r#"{"id":"msg_01","type":"message","role":"assistant","content":[],"model":"claude-3-haiku-20240307","stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":0}}"#;
+        let response: MessageResponse = serde_json::from_str(raw).unwrap();
+        assert_eq!(response.first_content(), None);
+        assert_eq!(response.last_content(), None);
+        assert_eq!(response.first_text(), None);
+        assert_eq!(response.text(), "");
+        assert_eq!(response.clone().expect_text().unwrap(), "");
+
+        let raw = r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-haiku-20240307","stop_reason":"end_turn","stop_sequence":null}"#;
+        let response: MessageResponse = serde_json::from_str(raw).unwrap();
+        assert!(response.content.is_empty());
+    }
+
+    #[test]
+    fn should_deserialize_server_tool_usage() {
+        let raw = r#"{"input_tokens":105,"output_tokens":6039,"server_tool_use":{"web_search_requests":2}}"#;
+        let usage: TokenUsage = serde_json::from_str(raw).unwrap();
+        assert_eq!(
+            usage.server_tool_use,
+            Some(ServerToolUsage {
+                web_search_requests: 2
+            })
+        );
+
+        let usage: TokenUsage =
+            serde_json::from_str(r#"{"input_tokens":1,"output_tokens":2}"#).unwrap();
+        assert_eq!(usage.server_tool_use, None);
+    }
+
+    #[test]
+    fn should_diff_token_usage() {
+        let baseline = TokenUsage {
+            input_tokens: 120,
+            output_tokens: 40,
+            cache_creation_input_tokens: None,
+            cache_read_input_tokens: None,
+            server_tool_use: None,
+        };
+        let candidate = TokenUsage {
+            input_tokens: 100,
+            output_tokens: 55,
+            cache_creation_input_tokens: None,
+            cache_read_input_tokens: None,
+            server_tool_use: None,
+        };
+
+        let diff = baseline.diff(&candidate);
+        assert_eq!(
+            diff,
+            TokenUsageDiff {
+                input_tokens: -20,
+                output_tokens: 15,
+            }
+        );
+        assert_eq!(diff.to_string(), "input tokens: -20, output tokens: +15");
+    }
+
+    #[test]
+    fn should_expect_text_response() {
+        let response = MessageResponse::mock_text("hello");
+        assert_eq!(response.expect_text().unwrap(), "hello");
+
+        let response = MessageResponse {
+            stop_reason: Some(StopReason::ToolUse),
+            ..MessageResponse::mock_text("Let me check the weather.")
+        };
+        assert!(matches!(
+            response.expect_text(),
+            Err(AnthropicError::UnexpectedToolUse)
+        ));
+    }
+
+    #[test]
+    fn should_convert_message_into_request() {
+        let message = Message {
+            role: Role::User,
+            content: vec![Content::Text {
+                text: "Hello".to_string(),
+                cache_control: None,
+            }],
+        };
+
+        let request = message.clone().into_request(Model::Claude3Haiku, 256);
+        assert_eq!(request.model, Model::Claude3Haiku);
+        assert_eq!(request.max_tokens, 256);
+        assert_eq!(request.messages, vec![message.clone()]);
+
+        let request = MessageRequest::from(message.clone());
+        assert_eq!(request.model, Model::default());
+        assert_eq!(request.messages, vec![message]);
+    }
+
     #[test]
     fn should_serialize_message() {
         let message = Message {
             role: Role::User,
-            content: vec![Content {
-                content_type: ContentType::Text,
+            content: vec![Content::Text {
                 text: "Hello World".to_string(),
+                cache_control: None,
             }],
         };
         assert_eq!(
@@ -316,9 +1688,9 @@ mod tests {
 
         let message = Message {
             role: Role::Assistant,
-            content: vec![Content {
-                content_type: ContentType::Text,
+            content: vec![Content::Text {
                 text: "Hello World".to_string(),
+                cache_control: None,
             }],
         };
         assert_eq!(
@@ -346,9 +1718,9 @@ mod tests {
         assert_eq!(message.role, Role::User);
         assert_eq!(
             message.content,
-            vec![Content {
-                content_type: ContentType::Text,
+            vec![Content::Text {
                 text: "Hello World".to_string(),
+                cache_control: None,
             }]
         );
 
@@ -363,10 +1735,489 @@ mod tests {
         assert_eq!(message.role, Role::Assistant);
         assert_eq!(
             message.content,
-            vec![Content {
-                content_type: ContentType::Text,
+            vec![Content::Text {
                 text: "Hello World".to_string(),
+                cache_control: None,
             }]
         );
     }
+
+    #[test]
+    fn should_serialize_tool_result_with_blocks() {
+        let content = Content::ToolResult {
+            tool_use_id: "toolu_01".to_string(),
+            content: vec![
+                Content::Text {
+                    text: "Screenshot of the page".to_string(),
+                    cache_control: None,
+                },
+                Content::Image {
+                    source: ImageSource::Base64 {
+                        media_type: ImageMediaType::Png,
+                        data: "iVBORw0KGgo=".to_string(),
+                    },
+                    cache_control: None,
+                },
+            ],
+            is_error: None,
+            cache_control: None,
+        };
+        let json = serde_json::json!({
+            "type": "tool_result",
+            "tool_use_id": "toolu_01",
+            "content": [
+                { "type": "text", "text": "Screenshot of the page" },
+                {
+                    "type": "image",
+                    "source": {
+                        "type": "base64",
+                        "media_type": "image/png",
+                        "data": "iVBORw0KGgo=",
+                    },
+                },
+            ],
+        });
+
+        assert_eq!(serde_json::to_value(&content).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Content>(json).unwrap(), content);
+    }
+
+    #[test]
+    fn should_round_trip_tool_use_into_request() {
+        let response: MessageResponse = serde_json::from_value(serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-20240620",
+            "content": [
+                { "type": "text", "text": "Let me check." },
+                {
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "get_weather",
+                    "input": { "location": "Paris", "unit": "celsius" },
+                },
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": { "input_tokens": 10, "output_tokens": 20 },
+        }))
+        .unwrap();
+
+        let assistant = Message {
+            role: Role::Assistant,
+            content: response.content,
+        };
+        let assistant: Message =
+            serde_json::from_value(serde_json::to_value(&assistant).unwrap()).unwrap();
+        let user = Message {
+            role: Role::User,
+            content: vec![Content::ToolResult {
+                tool_use_id: "toolu_01".to_string(),
+                content: vec![Content::Text {
+                    text: "15 degrees".to_string(),
+                    cache_control: None,
+                }],
+                is_error: None,
+                cache_control: None,
+            }],
+        };
+        let request = MessageRequest::new(Model::default(), 1024, vec![assistant, user]);
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["messages"],
+            serde_json::json!([
+                {
+                    "role": "assistant",
+                    "content": [
+                        { "type": "text", "text": "Let me check." },
+                        {
+                            "type": "tool_use",
+                            "id": "toolu_01",
+                            "name": "get_weather",
+                            "input": { "location": "Paris", "unit": "celsius" },
+                        },
+                    ],
+                },
+                {
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": "toolu_01",
+                        "content": [{ "type": "text", "text": "15 degrees" }],
+                    }],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn should_build_tool_results_message() {
+        let message = Message::tool_results(vec![
+            ("toolu_01".to_string(), serde_json::json!("15 degrees")),
+            (
+                "toolu_02".to_string(),
+                serde_json::json!({ "humidity": 80 }),
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            message,
+            Message {
+                role: Role::User,
+                content: vec![
+                    Content::ToolResult {
+                        tool_use_id: "toolu_01".to_string(),
+                        content: vec![Content::Text {
+                            text: "15 degrees".to_string(),
+                            cache_control: None,
+                        }],
+                        is_error: None,
+                        cache_control: None,
+                    },
+                    Content::ToolResult {
+                        tool_use_id: "toolu_02".to_string(),
+                        content: vec![Content::Text {
+                            text: r#"{"humidity":80}"#.to_string(),
+                            cache_control: None,
+                        }],
+                        is_error: None,
+                        cache_control: None,
+                    },
+                ],
+            }
+        );
+
+        assert!(matches!(
+            Message::tool_results(vec![(String::new(), serde_json::json!("ok"))]),
+            Err(AnthropicError::InvalidParameter {
+                field: "tool_use_id",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_deserialize_tool_result_with_string_content() {
+        let json = serde_json::json!({
+            "type": "tool_result",
+            "tool_use_id": "toolu_01",
+            "content": "15 degrees",
+            "is_error": false,
+        });
+        assert_eq!(
+            serde_json::from_value::<Content>(json).unwrap(),
+            Content::ToolResult {
+                tool_use_id: "toolu_01".to_string(),
+                content: vec![Content::Text {
+                    text: "15 degrees".to_string(),
+                    cache_control: None,
+                }],
+                is_error: Some(false),
+                cache_control: None,
+            }
+        );
+    }
+
+    #[test]
+    fn should_serialize_pdf_document() {
+        let content = Content::pdf_base64("JVBERi0xLjQ=")
+            .with_title("Report")
+            .with_context("Quarterly results");
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "document",
+                "source": {
+                    "type": "base64",
+                    "media_type": "application/pdf",
+                    "data": "JVBERi0xLjQ=",
+                },
+                "title": "Report",
+                "context": "Quarterly results",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Content::pdf_url("https://example.com/report.pdf")).unwrap(),
+            serde_json::json!({
+                "type": "document",
+                "source": { "type": "url", "url": "https://example.com/report.pdf" },
+            })
+        );
+
+        let request = MessageRequest::new(
+            Model::Claude35Sonnet20241022,
+            1024,
+            vec![Message {
+                role: Role::User,
+                content: ContentBuilder::new()
+                    .pdf_base64("JVBERi0xLjQ=")
+                    .text("Summarize this report")
+                    .build(),
+            }],
+        );
+        assert_eq!(request.required_betas(), vec![PDFS_BETA.to_string()]);
+    }
+
+    #[test]
+    fn should_serialize_thinking_content() {
+        let content = Content::Thinking {
+            thinking: "Let me think".to_string(),
+            signature: "sig".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "thinking",
+                "thinking": "Let me think",
+                "signature": "sig",
+            })
+        );
+
+        let content = Content::RedactedThinking {
+            data: "encrypted".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "redacted_thinking",
+                "data": "encrypted",
+            })
+        );
+    }
+
+    #[test]
+    fn should_serialize_thinking_config() {
+        let request = MessageRequest::default().with_thinking(2048);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["thinking"],
+            serde_json::json!({ "type": "enabled", "budget_tokens": 2048 })
+        );
+        assert_eq!(
+            serde_json::to_value(ThinkingConfig::Disabled).unwrap(),
+            serde_json::json!({ "type": "disabled" })
+        );
+        assert_eq!(
+            serde_json::to_value(MessageRequest::default())
+                .unwrap()
+                .get("thinking"),
+            None
+        );
+    }
+
+    #[test]
+    fn should_validate_tool_choice_against_tools() {
+        let tool = Tool::new("get_weather", serde_json::json!({ "type": "object" }));
+        let request = MessageRequest::default().with_tool_choice(ToolChoice::tool("get_weather"));
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["tool_choice"],
+            serde_json::json!({ "type": "tool", "name": "get_weather" })
+        );
+        assert!(matches!(
+            request.clone().validate(),
+            Err(AnthropicError::InvalidParameter {
+                field: "tool_choice",
+                ..
+            })
+        ));
+        assert!(request.with_tools(vec![tool.clone()]).validate().is_ok());
+
+        let request = MessageRequest::default().with_tool_choice(ToolChoice::Any);
+        assert!(request.clone().validate().is_err());
+        assert!(request.with_tools(vec![tool]).validate().is_ok());
+        assert!(MessageRequest::default()
+            .with_tool_choice(ToolChoice::None)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn should_validate_thinking_budget() {
+        let request = MessageRequest::default().with_max_tokens(4096);
+        assert!(request.clone().with_thinking(2048).validate().is_ok());
+        for budget_tokens in [512, 4096] {
+            assert!(matches!(
+                request.clone().with_thinking(budget_tokens).validate(),
+                Err(AnthropicError::InvalidParameter {
+                    field: "thinking",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn should_build_interleaved_content() {
+        let content = ContentBuilder::new()
+            .text("Compare these:")
+            .image_url("https://example.com/cat.png")
+            .text("and")
+            .image_base64(ImageMediaType::Jpeg, "/9j/4AAQ")
+            .build();
+
+        assert_eq!(
+            content,
+            vec![
+                Content::Text {
+                    text: "Compare these:".to_string(),
+                    cache_control: None,
+                },
+                Content::Image {
+                    source: ImageSource::Url {
+                        url: "https://example.com/cat.png".to_string(),
+                    },
+                    cache_control: None,
+                },
+                Content::Text {
+                    text: "and".to_string(),
+                    cache_control: None,
+                },
+                Content::Image {
+                    source: ImageSource::Base64 {
+                        media_type: ImageMediaType::Jpeg,
+                        data: "/9j/4AAQ".to_string(),
+                    },
+                    cache_control: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_edit_text_content_in_place() {
+        let image = Content::Image {
+            source: ImageSource::Url {
+                url: "https://example.com/cat.png".to_string(),
+            },
+            cache_control: None,
+        };
+        let mut contents = vec![
+            Content::Text {
+                text: "My email is jane@example.com".to_string(),
+                cache_control: None,
+            },
+            image.clone(),
+        ];
+
+        for content in contents.iter_mut() {
+            if let Some(text) = content.as_text_mut() {
+                *text = text.replace("jane@example.com", "[REDACTED]");
+            }
+        }
+
+        assert_eq!(
+            contents,
+            vec![
+                Content::Text {
+                    text: "My email is [REDACTED]".to_string(),
+                    cache_control: None,
+                },
+                image,
+            ]
+        );
+    }
+
+    #[test]
+    fn should_serialize_image_content() {
+        let content = Content::Image {
+            source: ImageSource::Base64 {
+                media_type: ImageMediaType::Png,
+                data: "iVBORw0KGgo=".to_string(),
+            },
+            cache_control: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "image",
+                "source": {
+                    "type": "base64",
+                    "media_type": "image/png",
+                    "data": "iVBORw0KGgo=",
+                },
+            })
+        );
+
+        let content = Content::Image {
+            source: ImageSource::Url {
+                url: "https://example.com/cat.png".to_string(),
+            },
+            cache_control: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!({
+                "type": "image",
+                "source": {
+                    "type": "url",
+                    "url": "https://example.com/cat.png",
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_fetch_image_as_base64() {
+        let png: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/cat.png")
+            .with_header("content-type", "image/png")
+            .with_body(png)
+            .create_async()
+            .await;
+
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        let url = format!("{}/cat.png", server.url());
+        let content = Content::image_fetch(&url, &client).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            content,
+            Content::Image {
+                source: ImageSource::Base64 {
+                    media_type: ImageMediaType::Png,
+                    data: STANDARD.encode(png),
+                },
+                cache_control: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_oversized_image() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/large.png")
+            .with_header("content-type", "image/png")
+            .with_body(vec![0u8; 64])
+            .create_async()
+            .await;
+
+        let client = Client::new(Config::new("anthropic-api-key").with_max_image_size(32)).unwrap();
+        let url = format!("{}/large.png", server.url());
+        assert!(matches!(
+            Content::image_fetch(&url, &client).await,
+            Err(AnthropicError::ImageTooLarge(32))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_unsupported_image_media_type() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/page")
+            .with_header("content-type", "text/html; charset=utf-8")
+            .with_body("<html></html>")
+            .create_async()
+            .await;
+
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        let url = format!("{}/page", server.url());
+        assert!(matches!(
+            Content::image_fetch(&url, &client).await,
+            Err(AnthropicError::UnsupportedMediaType(media_type)) if media_type == "text/html"
+        ));
+    }
 }
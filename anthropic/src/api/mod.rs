@@ -1,2 +1,5 @@
+pub mod batch;
+pub mod conversation;
 pub mod message;
 pub mod stream;
+pub mod tool;
@@ -1,11 +1,28 @@
+use bytes::Bytes;
 use core::fmt;
-use serde::{de::Error, Deserialize, Serialize};
-use std::str::FromStr;
+use futures_util::{future, stream, Stream, StreamExt};
+use serde::{de::DeserializeOwned, de::Error, Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
 
-use super::message::{MessageResponse, StopReason};
+use super::message::{Content, MessageResponse, StopReason, TokenUsage};
+use crate::error::{AnthropicError, ApiErrorDetail, ApiErrorResponse};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
+/// Event of a streamed response.
+///
+/// A stream starts with `message_start`, followed by a `content_block_start`, deltas and a
+/// `content_block_stop` for each content block, then `message_delta` and `message_stop`.
+/// `ping` events may come at any time and `error` reports a failure, e.g. an overloaded API,
+/// after which the stream ends.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum StreamEvent {
     Ping,
     MessageStart { message: MessageResponse },
@@ -14,6 +31,723 @@ pub enum StreamEvent {
     ContentBlockStart(ContentBlockStart),
     ContentBlockDelta(ContentBlockDelta),
     ContentBlockStop(ContentBlockStop),
+    Error { error: ApiErrorDetail },
+}
+
+impl StreamEvent {
+    /// Name of the event, as sent in the SSE `event:` field.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::Ping => "ping",
+            Self::MessageStart { .. } => "message_start",
+            Self::MessageDelta(_) => "message_delta",
+            Self::MessageStop => "message_stop",
+            Self::ContentBlockStart(_) => "content_block_start",
+            Self::ContentBlockDelta(_) => "content_block_delta",
+            Self::ContentBlockStop(_) => "content_block_stop",
+            Self::Error { .. } => "error",
+        }
+    }
+
+    /// Turns an `error` event into the error it reports, other events are returned as is.
+    pub fn into_result(self) -> Result<Self, AnthropicError> {
+        match self {
+            Self::Error { error } => Err(AnthropicError::Api(error_response(error, None))),
+            event => Ok(event),
+        }
+    }
+
+    /// Formats the event as a server-sent events frame.
+    pub fn to_sse_frame(&self) -> Result<Bytes, AnthropicError> {
+        let data = serde_json::to_string(self).map_err(AnthropicError::Serialize)?;
+        Ok(Bytes::from(format!(
+            "event: {}\ndata: {}\n\n",
+            self.event_type(),
+            data
+        )))
+    }
+
+    /// Parses a complete server-sent events capture, such as a saved `.sse` file.
+    pub fn parse_sse_log(log: &str) -> Result<Vec<StreamEvent>, AnthropicError> {
+        parse_sse_events(log).into_iter().collect()
+    }
+}
+
+/// Stream of events returned by [`Client::stream_message`].
+///
+/// Dropping the stream before the end of the response closes the underlying connection rather
+/// than returning it to the pool, so the API stops generating the rest of the response. No
+/// explicit cancellation is needed to cut costs when the output is no longer wanted.
+///
+/// An `error` event received mid-stream, e.g. an `overloaded_error`, is yielded as an
+/// [`AnthropicError::Api`] and ends the stream.
+///
+/// [`Client::stream_message`]: crate::client::Client::stream_message
+pub struct MessageStream {
+    events: Pin<Box<dyn Stream<Item = Result<StreamEvent, AnthropicError>> + Send>>,
+    raw_frames: Option<Arc<Mutex<Vec<String>>>>,
+    request_id: Option<String>,
+    usage: Option<TokenUsage>,
+    max_output_chars: Option<usize>,
+    output_chars: usize,
+    limit_reached: bool,
+    finished: bool,
+}
+
+impl MessageStream {
+    pub(crate) fn new(
+        events: impl Stream<Item = Result<StreamEvent, AnthropicError>> + Send + 'static,
+        raw_frames: Option<Arc<Mutex<Vec<String>>>>,
+    ) -> Self {
+        Self {
+            events: Box::pin(events),
+            raw_frames,
+            request_id: None,
+            usage: None,
+            max_output_chars: None,
+            output_chars: 0,
+            limit_reached: false,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn with_max_output_chars(mut self, max_output_chars: Option<usize>) -> Self {
+        self.max_output_chars = max_output_chars;
+        self
+    }
+
+    pub(crate) fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Value of the `request-id` header of the response, to share with Anthropic support.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Ends the stream after the output limit was exceeded, dropping the underlying connection.
+    fn stop_at_limit(&mut self) -> AnthropicError {
+        self.finished = true;
+        self.events = Box::pin(stream::empty());
+        AnthropicError::OutputLimitExceeded(self.max_output_chars.unwrap_or_default())
+    }
+
+    /// Ends the stream after an `error` event, returning the error it reports.
+    fn stop_at_error(&mut self, error: ApiErrorDetail) -> AnthropicError {
+        self.finished = true;
+        self.events = Box::pin(stream::empty());
+        AnthropicError::Api(error_response(error, self.request_id.clone()))
+    }
+
+    /// Cumulative usage of the events yielded so far, `None` until `message_start` is received.
+    ///
+    /// Output tokens are updated by every `message_delta`, e.g. to display live costs.
+    pub fn current_usage(&self) -> Option<TokenUsage> {
+        self.usage.clone()
+    }
+
+    /// Decoded text of the frames received so far, before parsing.
+    ///
+    /// Only recorded when [`Config::with_raw_stream_capture`] is enabled, empty otherwise.
+    ///
+    /// [`Config::with_raw_stream_capture`]: crate::config::Config::with_raw_stream_capture
+    pub fn raw_frames(&self) -> Vec<String> {
+        self.raw_frames
+            .as_ref()
+            .map(|frames| frames.lock().unwrap_or_else(|err| err.into_inner()).clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Stream for MessageStream {
+    type Item = Result<StreamEvent, AnthropicError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        if self.limit_reached {
+            return Poll::Ready(Some(Err(self.stop_at_limit())));
+        }
+
+        let mut poll = match self.events.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(StreamEvent::Error { error }))) => {
+                return Poll::Ready(Some(Err(self.stop_at_error(error))));
+            }
+            poll => poll,
+        };
+        match &mut poll {
+            Poll::Ready(Some(Ok(StreamEvent::ContentBlockDelta(content)))) => {
+                if let Some(max_output_chars) = self.max_output_chars {
+                    let remaining = max_output_chars - self.output_chars;
+                    let text = &mut content.delta.text;
+                    let chars = text.chars().count();
+                    if chars > remaining {
+                        if remaining == 0 {
+                            return Poll::Ready(Some(Err(self.stop_at_limit())));
+                        }
+                        self.limit_reached = true;
+                        if let Some((end, _)) = text.char_indices().nth(remaining) {
+                            text.truncate(end);
+                        }
+                    }
+                    self.output_chars += chars.min(remaining);
+                }
+            }
+            Poll::Ready(Some(Ok(StreamEvent::MessageStart { message }))) => {
+                self.usage = Some(message.usage.clone());
+            }
+            Poll::Ready(Some(Ok(StreamEvent::MessageDelta(delta)))) => {
+                if let Some(usage) = self.usage.as_mut() {
+                    usage.output_tokens = delta.usage.output_tokens;
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+/// Re-emits a stream of events as server-sent events frames, e.g. to proxy it to a browser.
+pub fn into_sse_frames(
+    events: impl Stream<Item = Result<StreamEvent, AnthropicError>>,
+) -> impl Stream<Item = Result<Bytes, AnthropicError>> {
+    events.map(|event| event.and_then(|event| event.to_sse_frame()))
+}
+
+/// Wraps a stream of events into an `axum` server-sent events response, e.g. to proxy it to a
+/// browser from a handler.
+///
+/// `ping` events are sent as SSE comments, which keep the connection alive without reaching the
+/// browser's event listeners, and further keep-alive comments are sent while the stream is idle.
+#[cfg(feature = "axum")]
+pub fn into_axum_sse(
+    events: impl Stream<Item = Result<StreamEvent, AnthropicError>> + Send + 'static,
+) -> axum::response::Sse<
+    impl Stream<Item = Result<axum::response::sse::Event, AnthropicError>> + Send + 'static,
+> {
+    let events = events.map(|event| event.and_then(|event| event.to_axum_event()));
+    axum::response::Sse::new(events).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
+#[cfg(feature = "axum")]
+impl StreamEvent {
+    /// Converts the event into an `axum` server-sent event, with `ping` as a comment.
+    pub fn to_axum_event(&self) -> Result<axum::response::sse::Event, AnthropicError> {
+        let event = axum::response::sse::Event::default();
+        if *self == Self::Ping {
+            return Ok(event.comment("ping"));
+        }
+        let data = serde_json::to_string(self).map_err(AnthropicError::Serialize)?;
+        Ok(event.event(self.event_type()).data(data))
+    }
+}
+
+/// Adapters over a stream of [`StreamEvent`].
+pub trait StreamEventExt: Stream<Item = Result<StreamEvent, AnthropicError>> + Sized {
+    /// Records latency metrics, measured from the moment this adapter is created.
+    fn with_stats(self) -> StatsStream<Self> {
+        StatsStream {
+            events: self,
+            started_at: Instant::now(),
+            first_token_at: None,
+            finished_at: None,
+            output_tokens: 0,
+        }
+    }
+
+    /// Yields the text of content deltas, filtered according to `options`.
+    fn text_deltas(
+        self,
+        options: TextDeltaOptions,
+    ) -> impl Stream<Item = Result<String, AnthropicError>> + Unpin {
+        let mut at_start = true;
+        Box::pin(self.filter_map(move |event| {
+            let item = match event {
+                Ok(StreamEvent::ContentBlockDelta(content))
+                    if content.delta.kind == ContentBlockKind::TextDelta =>
+                {
+                    let mut text = content.delta.text;
+                    if options.trim_leading_whitespace && at_start {
+                        text = text.trim_start().to_string();
+                    }
+                    if !text.is_empty() {
+                        at_start = false;
+                    }
+                    if options.skip_empty && text.is_empty() {
+                        None
+                    } else {
+                        Some(Ok(text))
+                    }
+                }
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            };
+            future::ready(item)
+        }))
+    }
+
+    /// Parses the input of the tool use being streamed after every `input_json_delta`, e.g. to
+    /// display a tool call as it forms.
+    ///
+    /// Yields `None` while the JSON accumulated for the current block can't be parsed as `T` yet,
+    /// typically because it is incomplete, and `Some` once it can.
+    fn tool_inputs<T: DeserializeOwned>(
+        self,
+    ) -> impl Stream<Item = Result<Option<T>, AnthropicError>> + Unpin {
+        let mut buffer = String::new();
+        Box::pin(self.filter_map(move |event| {
+            let item = match event {
+                Ok(StreamEvent::ContentBlockStart(_)) => {
+                    buffer.clear();
+                    None
+                }
+                Ok(StreamEvent::ContentBlockDelta(content))
+                    if content.delta.kind == ContentBlockKind::InputJsonDelta =>
+                {
+                    buffer.push_str(content.delta.partial_json.as_deref().unwrap_or_default());
+                    Some(Ok(serde_json::from_str(&buffer).ok()))
+                }
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            };
+            future::ready(item)
+        }))
+    }
+
+    /// Consumes the stream and assembles the complete response, see [`MessageAccumulator`].
+    fn accumulate(
+        self,
+    ) -> impl std::future::Future<Output = Result<MessageResponse, AnthropicError>> {
+        let mut events = Box::pin(self);
+        async move {
+            let mut accumulator = MessageAccumulator::new();
+            while let Some(event) = events.next().await {
+                accumulator.push(&event?)?;
+            }
+            accumulator.finish()
+        }
+    }
+
+    /// Buffers text deltas and yields complete sentences, e.g. to feed speech synthesis.
+    ///
+    /// A sentence ends at `.`, `!` or `?` followed by whitespace. A period after a single letter
+    /// or a common abbreviation (`Mr.`, `Dr.`, `e.g.`, ...) is not treated as a sentence end,
+    /// which is a heuristic rather than proper segmentation. The remaining text is flushed at
+    /// `message_stop` or when the stream ends.
+    fn sentences(self) -> impl Stream<Item = Result<String, AnthropicError>> + Unpin {
+        let state = (Box::pin(self), String::new(), VecDeque::new(), false);
+        Box::pin(stream::unfold(
+            state,
+            |(mut events, mut buffer, mut ready, mut finished)| async move {
+                loop {
+                    if let Some(item) = ready.pop_front() {
+                        return Some((item, (events, buffer, ready, finished)));
+                    }
+                    if finished {
+                        return None;
+                    }
+                    match events.next().await {
+                        Some(Ok(StreamEvent::ContentBlockDelta(content))) => {
+                            buffer.push_str(&content.delta.text);
+                            ready.extend(drain_sentences(&mut buffer).into_iter().map(Ok));
+                        }
+                        Some(Ok(StreamEvent::MessageStop)) | None => {
+                            finished = true;
+                            let rest = buffer.trim();
+                            if !rest.is_empty() {
+                                ready.push_back(Ok(rest.to_string()));
+                            }
+                            buffer.clear();
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(err)) => ready.push_back(Err(err)),
+                    }
+                }
+            },
+        ))
+    }
+}
+
+/// Filtering applied by [`StreamEventExt::text_deltas`], which keeps every delta by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextDeltaOptions {
+    /// Trims whitespace until the first non-whitespace text.
+    pub trim_leading_whitespace: bool,
+    /// Drops deltas with no text, including those emptied by trimming.
+    pub skip_empty: bool,
+}
+
+impl TextDeltaOptions {
+    pub fn with_trim_leading_whitespace(mut self, trim_leading_whitespace: bool) -> Self {
+        self.trim_leading_whitespace = trim_leading_whitespace;
+        self
+    }
+
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+}
+
+/// Latency metrics of a completed stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamStats {
+    /// Time until the first content delta, if any was received.
+    pub time_to_first_token: Option<Duration>,
+    /// Time until the stream ended.
+    pub duration: Duration,
+    /// Output tokens reported by the API.
+    pub output_tokens: u32,
+}
+
+impl StreamStats {
+    /// Output tokens generated per second over the whole stream.
+    pub fn tokens_per_second(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+        f64::from(self.output_tokens) / self.duration.as_secs_f64()
+    }
+}
+
+/// Stream recording [`StreamStats`] about the events it yields.
+///
+/// Created by [`StreamEventExt::with_stats`].
+pub struct StatsStream<S> {
+    events: S,
+    started_at: Instant,
+    first_token_at: Option<Instant>,
+    finished_at: Option<Instant>,
+    output_tokens: u32,
+}
+
+impl<S> StatsStream<S> {
+    /// Metrics of the stream, available once `message_stop` was received or the stream ended.
+    pub fn stats(&self) -> Option<StreamStats> {
+        let finished_at = self.finished_at?;
+        Some(StreamStats {
+            time_to_first_token: self
+                .first_token_at
+                .map(|first_token_at| first_token_at - self.started_at),
+            duration: finished_at - self.started_at,
+            output_tokens: self.output_tokens,
+        })
+    }
+}
+
+impl<S> Stream for StatsStream<S>
+where
+    S: Stream<Item = Result<StreamEvent, AnthropicError>> + Unpin,
+{
+    type Item = Result<StreamEvent, AnthropicError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.events.poll_next_unpin(cx);
+        if let Poll::Ready(item) = &poll {
+            let now = Instant::now();
+            match item {
+                Some(Ok(StreamEvent::MessageStart { message })) => {
+                    self.output_tokens = message.usage.output_tokens;
+                }
+                Some(Ok(StreamEvent::ContentBlockDelta(_))) => {
+                    self.first_token_at.get_or_insert(now);
+                }
+                Some(Ok(StreamEvent::MessageDelta(delta))) => {
+                    self.output_tokens = delta.usage.output_tokens;
+                }
+                Some(Ok(StreamEvent::MessageStop)) | None => {
+                    self.finished_at.get_or_insert(now);
+                }
+                _ => {}
+            }
+        }
+        poll
+    }
+}
+
+impl<S> StreamEventExt for S where S: Stream<Item = Result<StreamEvent, AnthropicError>> {}
+
+/// Assembles the [`MessageResponse`] of a stream from its events, e.g. to store the response
+/// once its deltas have been displayed.
+///
+/// The final response has the content blocks, `stop_reason` and output tokens of the whole
+/// stream, like the response of [`Client::create_message`].
+///
+/// [`Client::create_message`]: crate::client::Client::create_message
+#[derive(Debug, Clone, Default)]
+pub struct MessageAccumulator {
+    message: Option<MessageResponse>,
+    /// JSON input of the tool use being streamed, parsed once its block stops.
+    partial_json: String,
+}
+
+impl MessageAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Response assembled so far, `None` until `message_start` is received.
+    pub fn message(&self) -> Option<&MessageResponse> {
+        self.message.as_ref()
+    }
+
+    /// Applies `event` to the response.
+    ///
+    /// Fails with `InvalidStreamEvent` on events that don't fit the response, e.g. a delta for
+    /// a block that never started.
+    pub fn push(&mut self, event: &StreamEvent) -> Result<(), AnthropicError> {
+        match event {
+            StreamEvent::MessageStart { message } => self.message = Some(message.clone()),
+            StreamEvent::ContentBlockStart(start) => {
+                let content = &mut self.message_mut()?.content;
+                if usize::try_from(start.index).ok() != Some(content.len()) {
+                    return Err(AnthropicError::InvalidStreamEvent);
+                }
+                let block = start.content_block.clone();
+                content.push(match block.kind {
+                    ContentBlockKind::Text => Content::text(block.text),
+                    ContentBlockKind::ToolUse => Content::ToolUse {
+                        id: block.id.unwrap_or_default(),
+                        name: block.name.unwrap_or_default(),
+                        input: serde_json::Value::Null,
+                        cache_control: None,
+                    },
+                    ContentBlockKind::Thinking => Content::Thinking {
+                        thinking: block.thinking.unwrap_or_default(),
+                        signature: block.signature.unwrap_or_default(),
+                    },
+                    ContentBlockKind::RedactedThinking => Content::RedactedThinking {
+                        data: block.data.unwrap_or_default(),
+                    },
+                    _ => return Err(AnthropicError::InvalidStreamEvent),
+                });
+                self.partial_json.clear();
+            }
+            StreamEvent::ContentBlockDelta(delta) => {
+                let block = &delta.delta;
+                match (self.content_mut(delta.index)?, &block.kind) {
+                    (Content::Text { text, .. }, ContentBlockKind::TextDelta) => {
+                        text.push_str(&block.text)
+                    }
+                    (Content::ToolUse { .. }, ContentBlockKind::InputJsonDelta) => self
+                        .partial_json
+                        .push_str(block.partial_json.as_deref().unwrap_or_default()),
+                    (Content::Thinking { thinking, .. }, ContentBlockKind::ThinkingDelta) => {
+                        thinking.push_str(block.thinking.as_deref().unwrap_or_default())
+                    }
+                    (Content::Thinking { signature, .. }, ContentBlockKind::SignatureDelta) => {
+                        signature.push_str(block.signature.as_deref().unwrap_or_default())
+                    }
+                    _ => return Err(AnthropicError::InvalidStreamEvent),
+                }
+            }
+            StreamEvent::ContentBlockStop(stop) => {
+                let partial_json = std::mem::take(&mut self.partial_json);
+                if let Content::ToolUse { input, .. } = self.content_mut(stop.index)? {
+                    // Tools without parameters receive no delta at all.
+                    *input = if partial_json.is_empty() {
+                        serde_json::json!({})
+                    } else {
+                        serde_json::from_str(&partial_json)?
+                    };
+                }
+            }
+            StreamEvent::MessageDelta(delta) => {
+                let message = self.message_mut()?;
+                message.stop_reason = Some(delta.delta.stop_reason.clone());
+                message.stop_sequence = delta.delta.stop_sequence.clone();
+                message.usage.output_tokens = delta.usage.output_tokens;
+            }
+            StreamEvent::MessageStop | StreamEvent::Ping => {}
+            StreamEvent::Error { .. } => {
+                event.clone().into_result()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the assembled response, failing with `EmptyResponse` if no message was started.
+    pub fn finish(self) -> Result<MessageResponse, AnthropicError> {
+        self.message.ok_or(AnthropicError::EmptyResponse)
+    }
+
+    fn message_mut(&mut self) -> Result<&mut MessageResponse, AnthropicError> {
+        self.message
+            .as_mut()
+            .ok_or(AnthropicError::InvalidStreamEvent)
+    }
+
+    fn content_mut(&mut self, index: i64) -> Result<&mut Content, AnthropicError> {
+        let index = usize::try_from(index).map_err(|_| AnthropicError::InvalidStreamEvent)?;
+        self.message_mut()?
+            .content
+            .get_mut(index)
+            .ok_or(AnthropicError::InvalidStreamEvent)
+    }
+}
+
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "e.g", "i.e",
+];
+
+/// Removes the complete sentences at the start of `buffer` and returns them trimmed.
+fn drain_sentences(buffer: &mut String) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = buffer.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let Some(&(next_index, next)) = chars.peek() else {
+            break;
+        };
+        if !matches!(c, '.' | '!' | '?') || !next.is_whitespace() {
+            continue;
+        }
+        if c == '.' {
+            let word = buffer[start..index]
+                .rsplit(char::is_whitespace)
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            let is_initial = word.chars().count() == 1 && word.chars().all(char::is_alphabetic);
+            if is_initial || ABBREVIATIONS.contains(&word.as_str()) {
+                continue;
+            }
+        }
+        let sentence = buffer[start..next_index].trim();
+        if !sentence.is_empty() {
+            sentences.push(sentence.to_string());
+        }
+        start = next_index;
+    }
+    buffer.drain(..start);
+    sentences
+}
+
+/// Parses every `data:` payload of the server-sent events contained in `text`.
+pub(crate) fn parse_sse_events(text: &str) -> Vec<Result<StreamEvent, AnthropicError>> {
+    split_sse_frames(text).map(parse_sse_frame).collect()
+}
+
+/// Splits `text` into the non-empty server-sent events frames it contains.
+pub(crate) fn split_sse_frames(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\n\n").filter(|frame| !frame.trim().is_empty())
+}
+
+/// Parses the `data:` payload of a single server-sent events frame.
+///
+/// Lines may come in any order and, as allowed by the SSE spec, the payload may be spread over
+/// several `data:` lines which are joined with newlines. Other fields are ignored, since the
+/// event type is also part of the payload.
+pub(crate) fn parse_sse_frame(frame: &str) -> Result<StreamEvent, AnthropicError> {
+    let data: Vec<&str> = frame
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    if data.is_empty() {
+        return Err(AnthropicError::InvalidStreamEvent);
+    }
+    StreamEvent::from_str(&data.join("\n")).map_err(|_| AnthropicError::InvalidStreamEvent)
+}
+
+/// Error response reported by an `error` event, in the shape of HTTP error responses.
+fn error_response(error: ApiErrorDetail, request_id: Option<String>) -> ApiErrorResponse {
+    ApiErrorResponse {
+        error_type: "error".to_string(),
+        error,
+        retry_after: None,
+        rate_limit: None,
+        request_id,
+    }
+}
+
+/// Incremental parser of a response body received in chunks.
+pub(crate) trait BodyDecoder {
+    type Item;
+
+    /// Parses the items completed by `chunk`.
+    fn decode(&mut self, chunk: &[u8]) -> Vec<Result<Self::Item, AnthropicError>>;
+
+    /// Parses the remaining items once the body ended.
+    fn finish(&mut self) -> Vec<Result<Self::Item, AnthropicError>>;
+}
+
+/// Incremental server-sent events parser fed with the chunks of a response body.
+///
+/// Chunk boundaries are arbitrary, so a frame is only parsed once its terminating blank line has
+/// arrived and any partial frame is kept for the next chunk.
+pub(crate) struct SseDecoder {
+    buffer: String,
+    pending: Vec<u8>,
+    raw_frames: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl SseDecoder {
+    pub(crate) fn new(raw_frames: Option<Arc<Mutex<Vec<String>>>>) -> Self {
+        Self {
+            buffer: String::new(),
+            pending: Vec::new(),
+            raw_frames,
+        }
+    }
+
+    /// Parses the frames completed by `chunk`.
+    pub(crate) fn decode(&mut self, chunk: &[u8]) -> Vec<Result<StreamEvent, AnthropicError>> {
+        self.pending.extend_from_slice(chunk);
+        match std::str::from_utf8(&self.pending) {
+            Ok(text) => {
+                self.buffer.push_str(text);
+                self.pending.clear();
+            }
+            // A multi-byte character split across chunks is completed by the next chunk.
+            Err(err) if err.error_len().is_none() => {
+                let valid = err.valid_up_to();
+                self.buffer
+                    .push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                self.pending.drain(..valid);
+            }
+            Err(err) => {
+                self.pending.clear();
+                return vec![Err(AnthropicError::Utf8Error(err))];
+            }
+        }
+        // Frames may also be separated by CRLF line endings, possibly split across chunks.
+        if self.buffer.contains('\r') {
+            self.buffer = self.buffer.replace("\r\n", "\n");
+        }
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.find("\n\n") {
+            let frame: String = self.buffer.drain(..end + 2).collect();
+            events.extend(self.parse_frame(&frame[..end]));
+        }
+        events
+    }
+
+    /// Parses the last frame when the body ends without a terminating blank line.
+    pub(crate) fn finish(&mut self) -> Vec<Result<StreamEvent, AnthropicError>> {
+        let pending = std::mem::take(&mut self.pending);
+        if let Err(err) = std::str::from_utf8(&pending) {
+            return vec![Err(AnthropicError::Utf8Error(err))];
+        }
+        let frame = std::mem::take(&mut self.buffer);
+        self.parse_frame(&frame).into_iter().collect()
+    }
+
+    fn parse_frame(&self, frame: &str) -> Option<Result<StreamEvent, AnthropicError>> {
+        if frame.trim().is_empty() {
+            return None;
+        }
+        if let Some(raw_frames) = &self.raw_frames {
+            raw_frames
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(frame.to_string());
+        }
+        Some(parse_sse_frame(frame))
+    }
 }
 
 impl FromStr for StreamEvent {
@@ -48,57 +782,86 @@ impl FromStr for StreamEvent {
                 Ok(StreamEvent::MessageDelta(message))
             }
             "message_stop" => Ok(StreamEvent::MessageStop),
+            "error" => {
+                let error: ApiErrorDetail = serde_json::from_value(value["error"].clone())?;
+                Ok(StreamEvent::Error { error })
+            }
             _ => Ok(StreamEvent::MessageStop),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct MessageDelta {
     pub delta: MessageDeltaStop,
     pub usage: StreamUsageTokens,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct MessageDeltaStop {
     pub stop_reason: StopReason,
     pub stop_sequence: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct StreamUsageTokens {
     pub output_tokens: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ContentBlockStart {
     pub index: i64,
     pub content_block: ContentBlock,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ContentBlockDelta {
     pub index: i64,
     pub delta: ContentBlock,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ContentBlockStop {
     pub index: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ContentBlock {
     #[serde(rename = "type")]
     pub kind: ContentBlockKind,
+    #[serde(default)]
     pub text: String,
+    /// Fragment of the JSON input of a tool use block, set for `input_json_delta`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_json: Option<String>,
+    /// Fragment of the reasoning of a thinking block, set for `thinking_delta`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
+    /// Signature of a thinking block, set for `signature_delta` once its reasoning is complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Id of a tool use block, set when it starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Name of the tool of a tool use block, set when it starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Encrypted reasoning of a redacted thinking block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ContentBlockKind {
     Text,
     TextDelta,
+    ToolUse,
+    InputJsonDelta,
+    Thinking,
+    ThinkingDelta,
+    SignatureDelta,
+    RedactedThinking,
 }
 
 impl fmt::Display for ContentBlockKind {
@@ -106,13 +869,19 @@ impl fmt::Display for ContentBlockKind {
         match self {
             Self::Text => write!(f, "text"),
             Self::TextDelta => write!(f, "text_delta"),
+            Self::ToolUse => write!(f, "tool_use"),
+            Self::InputJsonDelta => write!(f, "input_json_delta"),
+            Self::Thinking => write!(f, "thinking"),
+            Self::ThinkingDelta => write!(f, "thinking_delta"),
+            Self::SignatureDelta => write!(f, "signature_delta"),
+            Self::RedactedThinking => write!(f, "redacted_thinking"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{api::message::RoleResponse, prelude::Model};
+    use crate::{api::message::RoleResponse, error::ApiErrorType, prelude::Model};
 
     use super::*;
     use pretty_assertions::assert_eq;
@@ -198,6 +967,648 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_parse_sse_log() {
+        let log = concat!(
+            "event: message_start\n",
+            r#"data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+            "\n\n",
+            "event: content_block_start\n",
+            r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            "\n\n",
+            "event: ping\n",
+            r#"data: {"type": "ping"}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#,
+            "\n\n",
+            "event: content_block_stop\n",
+            r#"data: {"type":"content_block_stop","index":0}"#,
+            "\n\n",
+            "event: message_delta\n",
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#,
+            "\n\n",
+            "event: message_stop\n",
+            r#"data: {"type":"message_stop"}"#,
+            "\n\n",
+        );
+
+        let events = StreamEvent::parse_sse_log(log).unwrap();
+        assert_eq!(events.len(), 7);
+        assert!(
+            matches!(&events[0], StreamEvent::MessageStart { message } if message.id == "msg_01")
+        );
+        assert_eq!(
+            events[1],
+            StreamEvent::ContentBlockStart(ContentBlockStart {
+                index: 0,
+                content_block: ContentBlock {
+                    kind: ContentBlockKind::Text,
+                    text: String::new(),
+                    partial_json: None,
+                    thinking: None,
+                    signature: None,
+                    id: None,
+                    name: None,
+                    data: None,
+                },
+            })
+        );
+        assert_eq!(events[2], StreamEvent::Ping);
+        assert_eq!(
+            events[3],
+            StreamEvent::ContentBlockDelta(ContentBlockDelta {
+                index: 0,
+                delta: ContentBlock {
+                    kind: ContentBlockKind::TextDelta,
+                    text: "Hello".to_string(),
+                    partial_json: None,
+                    thinking: None,
+                    signature: None,
+                    id: None,
+                    name: None,
+                    data: None,
+                },
+            })
+        );
+        assert_eq!(
+            events[4],
+            StreamEvent::ContentBlockStop(ContentBlockStop { index: 0 })
+        );
+        assert_eq!(
+            events[5],
+            StreamEvent::MessageDelta(MessageDelta {
+                delta: MessageDeltaStop {
+                    stop_reason: StopReason::EndTurn,
+                    stop_sequence: None,
+                },
+                usage: StreamUsageTokens { output_tokens: 15 },
+            })
+        );
+        assert_eq!(events[6], StreamEvent::MessageStop);
+    }
+
+    #[tokio::test]
+    async fn should_round_trip_sse_frames() {
+        let events = vec![
+            StreamEvent::Ping,
+            StreamEvent::ContentBlockStart(ContentBlockStart {
+                index: 0,
+                content_block: ContentBlock {
+                    kind: ContentBlockKind::Text,
+                    text: String::new(),
+                    partial_json: None,
+                    thinking: None,
+                    signature: None,
+                    id: None,
+                    name: None,
+                    data: None,
+                },
+            }),
+            StreamEvent::ContentBlockDelta(ContentBlockDelta {
+                index: 0,
+                delta: ContentBlock {
+                    kind: ContentBlockKind::TextDelta,
+                    text: "Hello!".to_string(),
+                    partial_json: None,
+                    thinking: None,
+                    signature: None,
+                    id: None,
+                    name: None,
+                    data: None,
+                },
+            }),
+            StreamEvent::ContentBlockStop(ContentBlockStop { index: 0 }),
+            StreamEvent::MessageDelta(MessageDelta {
+                delta: MessageDeltaStop {
+                    stop_reason: StopReason::EndTurn,
+                    stop_sequence: None,
+                },
+                usage: StreamUsageTokens { output_tokens: 3 },
+            }),
+            StreamEvent::MessageStop,
+        ];
+
+        let frames: Vec<Bytes> =
+            into_sse_frames(futures_util::stream::iter(events.iter().map(|event| {
+                Ok(StreamEvent::from_str(&serde_json::to_string(event).unwrap()).unwrap())
+            })))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(
+            frames[0],
+            Bytes::from("event: ping\ndata: {\"type\":\"ping\"}\n\n")
+        );
+
+        let log: String = frames
+            .iter()
+            .map(|frame| std::str::from_utf8(frame).unwrap())
+            .collect();
+        assert_eq!(StreamEvent::parse_sse_log(&log).unwrap(), events);
+    }
+
+    fn text_delta_events(deltas: &[&str]) -> Vec<Result<StreamEvent, AnthropicError>> {
+        deltas
+            .iter()
+            .map(|text| {
+                Ok(StreamEvent::ContentBlockDelta(ContentBlockDelta {
+                    index: 0,
+                    delta: ContentBlock {
+                        kind: ContentBlockKind::TextDelta,
+                        text: text.to_string(),
+                        partial_json: None,
+                        thinking: None,
+                        signature: None,
+                        id: None,
+                        name: None,
+                        data: None,
+                    },
+                }))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn should_parse_tool_input_once_complete() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Weather {
+            location: String,
+        }
+
+        let mut events = vec![Ok(StreamEvent::ContentBlockStart(ContentBlockStart {
+            index: 1,
+            content_block: ContentBlock {
+                kind: ContentBlockKind::ToolUse,
+                text: String::new(),
+                partial_json: None,
+                thinking: None,
+                signature: None,
+                id: None,
+                name: None,
+                data: None,
+            },
+        }))];
+        for partial_json in [r#"{"locat"#, r#"ion": "Par"#, r#"is"}"#] {
+            events.push(Ok(StreamEvent::ContentBlockDelta(ContentBlockDelta {
+                index: 1,
+                delta: ContentBlock {
+                    kind: ContentBlockKind::InputJsonDelta,
+                    text: String::new(),
+                    partial_json: Some(partial_json.to_string()),
+                    thinking: None,
+                    signature: None,
+                    id: None,
+                    name: None,
+                    data: None,
+                },
+            })));
+        }
+
+        let inputs: Vec<Option<Weather>> = futures_util::stream::iter(events)
+            .tool_inputs()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            inputs,
+            vec![
+                None,
+                None,
+                Some(Weather {
+                    location: "Paris".to_string()
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn should_deserialize_input_json_delta_event() {
+        let raw = r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\": \"San Fra"}}"#;
+        let StreamEvent::ContentBlockDelta(content) = raw.parse().unwrap() else {
+            panic!("Expected 'content_block_delta' event");
+        };
+        assert_eq!(content.delta.kind, ContentBlockKind::InputJsonDelta);
+        assert_eq!(
+            content.delta.partial_json.as_deref(),
+            Some(r#"{"location": "San Fra"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_filter_text_deltas() {
+        let deltas = ["\n", "  Hello", "", " world", ""];
+
+        let texts: Vec<String> = futures_util::stream::iter(text_delta_events(&deltas))
+            .text_deltas(TextDeltaOptions::default())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(texts, deltas);
+
+        let options = TextDeltaOptions::default()
+            .with_trim_leading_whitespace(true)
+            .with_skip_empty(true);
+        let texts: Vec<String> = futures_util::stream::iter(text_delta_events(&deltas))
+            .text_deltas(options)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(texts, vec!["Hello", " world"]);
+
+        let options = TextDeltaOptions::default().with_trim_leading_whitespace(true);
+        let texts: Vec<String> = futures_util::stream::iter(text_delta_events(&deltas))
+            .text_deltas(options)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(texts, vec!["", "Hello", "", " world", ""]);
+    }
+
+    #[tokio::test]
+    async fn should_yield_complete_sentences() {
+        let deltas = [
+            "Hello the",
+            "re! Dr. Smith ",
+            "arrived at 3.",
+            "5 p.m. Did you see ",
+            "him? I think",
+            " so",
+        ];
+        let mut events = text_delta_events(&deltas);
+        events.push(Ok(StreamEvent::MessageStop));
+
+        let sentences: Vec<String> = futures_util::stream::iter(events)
+            .sentences()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(
+            sentences,
+            vec![
+                "Hello there!",
+                "Dr. Smith arrived at 3.5 p.m.",
+                "Did you see him?",
+                "I think so",
+            ]
+        );
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn should_convert_events_to_axum_sse() {
+        use axum::response::IntoResponse;
+
+        let events = vec![Ok(StreamEvent::Ping), Ok(StreamEvent::MessageStop)];
+        let response = into_axum_sse(futures_util::stream::iter(events)).into_response();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            ": ping\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n"
+        );
+    }
+
+    #[test]
+    fn should_parse_frame_fields_in_any_order() {
+        let frame = "data: {\"type\": \"ping\"}\nevent: ping";
+        assert_eq!(parse_sse_frame(frame).unwrap(), StreamEvent::Ping);
+
+        let frame = ": comment\r\nevent: message_stop\r\ndata:{\"type\": \"message_stop\"}";
+        assert_eq!(parse_sse_frame(frame).unwrap(), StreamEvent::MessageStop);
+    }
+
+    #[tokio::test]
+    async fn should_accumulate_recorded_events_into_response() {
+        let log = concat!(
+            "event: message_start\n",
+            r#"data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20241022","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":472,"output_tokens":2}}}"#,
+            "\n\n",
+            "event: content_block_start\n",
+            r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            "\n\n",
+            "event: ping\n",
+            r#"data: {"type":"ping"}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me check "}}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"the weather."}}"#,
+            "\n\n",
+            "event: content_block_stop\n",
+            r#"data: {"type":"content_block_stop","index":0}"#,
+            "\n\n",
+            "event: content_block_start\n",
+            r#"data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01","name":"get_weather","input":{}}}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\":"}}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":" \"Paris\"}"}}"#,
+            "\n\n",
+            "event: content_block_stop\n",
+            r#"data: {"type":"content_block_stop","index":1}"#,
+            "\n\n",
+            "event: message_delta\n",
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":89}}"#,
+            "\n\n",
+            "event: message_stop\n",
+            r#"data: {"type":"message_stop"}"#,
+            "\n\n",
+        );
+        let events = StreamEvent::parse_sse_log(log).unwrap();
+
+        let response = stream::iter(events.into_iter().map(Ok))
+            .accumulate()
+            .await
+            .unwrap();
+        assert_eq!(response.id, "msg_01");
+        assert_eq!(
+            response.content,
+            vec![
+                Content::text("Let me check the weather."),
+                Content::ToolUse {
+                    id: "toolu_01".to_string(),
+                    name: "get_weather".to_string(),
+                    input: serde_json::json!({ "location": "Paris" }),
+                    cache_control: None,
+                },
+            ]
+        );
+        assert_eq!(response.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(response.usage.input_tokens, 472);
+        assert_eq!(response.usage.output_tokens, 89);
+    }
+
+    #[test]
+    fn should_reject_delta_without_started_block() {
+        let mut accumulator = MessageAccumulator::new();
+        accumulator
+            .push(&StreamEvent::MessageStart {
+                message: MessageResponse::from(Vec::new()),
+            })
+            .unwrap();
+        let delta =
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#
+                .parse()
+                .unwrap();
+        assert!(matches!(
+            accumulator.push(&delta),
+            Err(AnthropicError::InvalidStreamEvent)
+        ));
+        assert!(matches!(
+            MessageAccumulator::new().finish(),
+            Err(AnthropicError::EmptyResponse)
+        ));
+    }
+
+    #[test]
+    fn should_parse_error_event() {
+        let frame = concat!(
+            "event: error\n",
+            r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        );
+        let event = parse_sse_frame(frame).unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::Error {
+                error: ApiErrorDetail {
+                    error_type: ApiErrorType::Overloaded,
+                    message: "Overloaded".to_string(),
+                },
+            }
+        );
+        assert_eq!(event.event_type(), "error");
+        assert_eq!(
+            event.to_sse_frame().unwrap(),
+            Bytes::from(format!("{frame}\n\n"))
+        );
+
+        let err = event.into_result().unwrap_err();
+        assert!(err.is_retryable());
+        assert!(matches!(
+            err,
+            AnthropicError::Api(ApiErrorResponse { error, .. }) if error.error_type == ApiErrorType::Overloaded
+        ));
+        assert_eq!(StreamEvent::Ping.into_result().unwrap(), StreamEvent::Ping);
+    }
+
+    #[test]
+    fn should_parse_thinking_deltas() {
+        let event: StreamEvent = r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Let me think"}}"#
+            .parse()
+            .unwrap();
+        let StreamEvent::ContentBlockDelta(content) = event else {
+            panic!("expected a content block delta");
+        };
+        assert_eq!(content.delta.kind, ContentBlockKind::ThinkingDelta);
+        assert_eq!(content.delta.thinking.as_deref(), Some("Let me think"));
+
+        let event: StreamEvent = r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#
+            .parse()
+            .unwrap();
+        let StreamEvent::ContentBlockDelta(content) = event else {
+            panic!("expected a content block delta");
+        };
+        assert_eq!(content.delta.kind, ContentBlockKind::SignatureDelta);
+        assert_eq!(content.delta.signature.as_deref(), Some("sig"));
+    }
+
+    #[test]
+    fn should_join_multiple_data_lines() {
+        let frame = concat!(
+            "event: content_block_delta\n",
+            "data: {\"type\": \"content_block_delta\", \"index\": 0,\n",
+            "data: \"delta\": {\"type\": \"text_delta\", \"text\": \"Hello\"}}",
+        );
+        let StreamEvent::ContentBlockDelta(content) = parse_sse_frame(frame).unwrap() else {
+            panic!("expected a content block delta");
+        };
+        assert_eq!(content.delta.text, "Hello");
+
+        assert!(matches!(
+            parse_sse_frame("event: ping"),
+            Err(AnthropicError::InvalidStreamEvent)
+        ));
+    }
+
+    #[test]
+    fn should_decode_character_split_across_chunks() {
+        let frame = "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hé 🦀\"}}\n\n";
+        let crab = frame.find('🦀').unwrap();
+        let (first, second) = frame.as_bytes().split_at(crab + 2);
+
+        let mut decoder = SseDecoder::new(None);
+        assert!(decoder.decode(first).is_empty());
+        let events = decoder.decode(second);
+        let Ok(StreamEvent::ContentBlockDelta(content)) = &events[0] else {
+            panic!("expected a content block delta");
+        };
+        assert_eq!(content.delta.text, "Hé 🦀");
+    }
+
+    #[test]
+    fn should_report_invalid_utf8_in_stream() {
+        let mut decoder = SseDecoder::new(None);
+        assert!(matches!(
+            decoder.decode(&[0xff])[..],
+            [Err(AnthropicError::Utf8Error(_))]
+        ));
+
+        assert!(decoder
+            .decode("data: 🦀".as_bytes().split_at(8).0)
+            .is_empty());
+        assert!(matches!(
+            decoder.finish()[..],
+            [Err(AnthropicError::Utf8Error(_))]
+        ));
+    }
+
+    #[test]
+    fn should_buffer_frames_split_across_chunks() {
+        let mut decoder = SseDecoder::new(None);
+        assert!(decoder.decode(b"event: ping\ndata: {\"ty").is_empty());
+
+        let events = decoder.decode(b"pe\": \"ping\"}\n\nevent: message_stop\ndata: ");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].as_ref().unwrap(), &StreamEvent::Ping);
+
+        assert!(decoder.decode(br#"{"type": "message_stop"}"#).is_empty());
+        let events = decoder.finish();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].as_ref().unwrap(), &StreamEvent::MessageStop);
+        assert!(decoder.finish().is_empty());
+
+        assert!(decoder
+            .decode(b"data: {\"type\": \"ping\"}\r\n\r")
+            .is_empty());
+        assert_eq!(decoder.decode(b"\n").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_stop_stream_at_max_output_chars() {
+        let events = text_delta_events(&["Hello ", "world", "!"]);
+        let mut stream = MessageStream::new(futures_util::stream::iter(events), None)
+            .with_max_output_chars(Some(8));
+
+        let mut texts = Vec::new();
+        while let Some(Ok(StreamEvent::ContentBlockDelta(content))) = stream.next().await {
+            texts.push(content.delta.text);
+        }
+        assert_eq!(texts, vec!["Hello ", "wo"]);
+        assert!(stream.next().await.is_none());
+
+        let events = text_delta_events(&["Hello", " world"]);
+        let results: Vec<_> = MessageStream::new(futures_util::stream::iter(events), None)
+            .with_max_output_chars(Some(5))
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(matches!(
+            results[1],
+            Err(AnthropicError::OutputLimitExceeded(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_track_usage_mid_stream() {
+        let message: MessageResponse = serde_json::from_str(r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}"#).unwrap();
+        let message_delta = |output_tokens| {
+            Ok(StreamEvent::MessageDelta(MessageDelta {
+                delta: MessageDeltaStop {
+                    stop_reason: StopReason::EndTurn,
+                    stop_sequence: None,
+                },
+                usage: StreamUsageTokens { output_tokens },
+            }))
+        };
+        let events = vec![
+            Ok(StreamEvent::MessageStart { message }),
+            message_delta(20),
+            message_delta(45),
+            Ok(StreamEvent::MessageStop),
+        ];
+
+        let mut stream = MessageStream::new(futures_util::stream::iter(events), None);
+        assert_eq!(stream.current_usage(), None);
+
+        let mut output_tokens = Vec::new();
+        while stream.next().await.is_some() {
+            let usage = stream.current_usage().unwrap();
+            assert_eq!(usage.input_tokens, 9);
+            output_tokens.push(usage.output_tokens);
+        }
+        assert_eq!(output_tokens, vec![1, 20, 45, 45]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_compute_stream_stats() {
+        let message: MessageResponse = serde_json::from_str(r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}"#).unwrap();
+        let delta = StreamEvent::ContentBlockDelta(ContentBlockDelta {
+            index: 0,
+            delta: ContentBlock {
+                kind: ContentBlockKind::TextDelta,
+                text: "Hello".to_string(),
+                partial_json: None,
+                thinking: None,
+                signature: None,
+                id: None,
+                name: None,
+                data: None,
+            },
+        });
+        let script = vec![
+            (100, StreamEvent::MessageStart { message }),
+            (200, delta.clone()),
+            (300, delta),
+            (
+                400,
+                StreamEvent::MessageDelta(MessageDelta {
+                    delta: MessageDeltaStop {
+                        stop_reason: StopReason::EndTurn,
+                        stop_sequence: None,
+                    },
+                    usage: StreamUsageTokens { output_tokens: 50 },
+                }),
+            ),
+            (0, StreamEvent::MessageStop),
+        ];
+        let events = Box::pin(futures_util::stream::iter(script).then(
+            |(delay, event)| async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(event)
+            },
+        ));
+
+        let mut events = events.with_stats();
+        while let Some(event) = events.next().await {
+            if event.unwrap() != StreamEvent::MessageStop {
+                assert_eq!(events.stats(), None);
+            }
+        }
+
+        let stats = events.stats().unwrap();
+        assert_eq!(stats.time_to_first_token, Some(Duration::from_millis(300)));
+        assert_eq!(stats.duration, Duration::from_millis(1000));
+        assert_eq!(stats.output_tokens, 50);
+        assert_eq!(stats.tokens_per_second(), 50.0);
+    }
+
+    #[test]
+    fn should_fail_to_parse_invalid_sse_log() {
+        assert!(matches!(
+            StreamEvent::parse_sse_log("event: ping\n\n"),
+            Err(AnthropicError::InvalidStreamEvent)
+        ));
+    }
+
     #[test]
     fn should_deserialize_message_stop_event() {
         let raw = r#"{"type":"message_stop"}"#;
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+/// A tool the model may call, described by a JSON Schema of its input.
+///
+/// Calls are returned as [`Content::ToolUse`] blocks and answered with [`Content::ToolResult`].
+///
+/// [`Content::ToolUse`]: crate::api::message::Content::ToolUse
+/// [`Content::ToolResult`]: crate::api::message::Content::ToolResult
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tool {
+    /// Name of the tool, referenced by the tool use blocks of responses.
+    pub name: String,
+
+    /// What the tool does and when to use it, which the model relies on to pick tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON Schema of the tool input, which must describe an object.
+    ///
+    /// Simple schemas can be built with [`ToolInputSchema`], complex ones written as raw JSON.
+    pub input_schema: serde_json::Value,
+}
+
+impl Tool {
+    pub fn new(name: impl Into<String>, input_schema: serde_json::Value) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            input_schema,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// How the model should use the tools of a request, see [`MessageRequest::with_tool_choice`].
+///
+/// [`MessageRequest::with_tool_choice`]: crate::api::message::MessageRequest::with_tool_choice
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to use a tool.
+    Auto,
+    /// The model must use one of the tools.
+    Any,
+    /// The model must use the named tool.
+    Tool { name: String },
+    /// The model must not use any tool.
+    None,
+}
+
+impl ToolChoice {
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self::Tool { name: name.into() }
+    }
+}
+
+/// Builder of the JSON Schema of a tool input whose properties are all of simple types.
+///
+/// ```
+/// use anthropic_rs::api::tool::{Tool, ToolInputSchema};
+///
+/// let schema = ToolInputSchema::object()
+///     .string_prop("city", true)
+///     .boolean_prop("celsius", false)
+///     .build();
+/// let tool = Tool::new("get_weather", schema);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolInputSchema {
+    properties: serde_json::Map<String, serde_json::Value>,
+    required: Vec<String>,
+}
+
+impl ToolInputSchema {
+    /// Starts an object schema without properties.
+    pub fn object() -> Self {
+        Self::default()
+    }
+
+    pub fn string_prop(self, name: impl Into<String>, required: bool) -> Self {
+        self.typed_prop(name, "string", required)
+    }
+
+    pub fn number_prop(self, name: impl Into<String>, required: bool) -> Self {
+        self.typed_prop(name, "number", required)
+    }
+
+    pub fn integer_prop(self, name: impl Into<String>, required: bool) -> Self {
+        self.typed_prop(name, "integer", required)
+    }
+
+    pub fn boolean_prop(self, name: impl Into<String>, required: bool) -> Self {
+        self.typed_prop(name, "boolean", required)
+    }
+
+    /// Adds a property described by a raw JSON Schema, e.g. an enum or a nested object.
+    pub fn prop(
+        mut self,
+        name: impl Into<String>,
+        schema: serde_json::Value,
+        required: bool,
+    ) -> Self {
+        let name = name.into();
+        self.required.retain(|required| *required != name);
+        if required {
+            self.required.push(name.clone());
+        }
+        self.properties.insert(name, schema);
+        self
+    }
+
+    fn typed_prop(self, name: impl Into<String>, kind: &str, required: bool) -> Self {
+        self.prop(name, serde_json::json!({ "type": kind }), required)
+    }
+
+    /// Returns the schema, to pass to [`Tool::new`].
+    pub fn build(self) -> serde_json::Value {
+        let mut schema = serde_json::json!({
+            "type": "object",
+            "properties": self.properties,
+        });
+        if !self.required.is_empty() {
+            schema["required"] = self.required.into();
+        }
+        schema
+    }
+}
+
+impl From<ToolInputSchema> for serde_json::Value {
+    fn from(schema: ToolInputSchema) -> Self {
+        schema.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_serialize_tool() {
+        let tool = Tool::new(
+            "get_weather",
+            serde_json::json!({
+                "type": "object",
+                "properties": { "location": { "type": "string" } },
+                "required": ["location"],
+            }),
+        );
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap(),
+            serde_json::json!({
+                "name": "get_weather",
+                "input_schema": {
+                    "type": "object",
+                    "properties": { "location": { "type": "string" } },
+                    "required": ["location"],
+                },
+            })
+        );
+
+        let tool = tool.with_description("Get the current weather in a given location");
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap()["description"],
+            "Get the current weather in a given location"
+        );
+    }
+
+    #[test]
+    fn should_serialize_tool_choice() {
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Auto).unwrap(),
+            serde_json::json!({ "type": "auto" })
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Any).unwrap(),
+            serde_json::json!({ "type": "any" })
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::tool("get_weather")).unwrap(),
+            serde_json::json!({ "type": "tool", "name": "get_weather" })
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::None).unwrap(),
+            serde_json::json!({ "type": "none" })
+        );
+    }
+
+    #[test]
+    fn should_build_input_schema() {
+        let schema = ToolInputSchema::object()
+            .string_prop("city", true)
+            .integer_prop("days", false)
+            .prop(
+                "unit",
+                serde_json::json!({ "type": "string", "enum": ["celsius", "fahrenheit"] }),
+                true,
+            )
+            .number_prop("days", true)
+            .build();
+        assert_eq!(
+            schema,
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "city": { "type": "string" },
+                    "days": { "type": "number" },
+                    "unit": { "type": "string", "enum": ["celsius", "fahrenheit"] },
+                },
+                "required": ["city", "unit", "days"],
+            })
+        );
+        assert_eq!(
+            serde_json::Value::from(ToolInputSchema::object()),
+            serde_json::json!({ "type": "object", "properties": {} })
+        );
+    }
+}
@@ -0,0 +1,14 @@
+/// Receives the exact bytes exchanged with the API, e.g. to keep an audit log in regulated
+/// environments.
+///
+/// Request bodies are reported as serialized, before being sent. Response bodies are reported as
+/// received, chunk by chunk for streams and including error responses. Headers are not reported
+/// since they carry the API key.
+///
+/// Methods are called from the task driving the request, so they should return quickly, e.g. by
+/// forwarding the bytes to a channel.
+pub trait AuditSink: Send + Sync {
+    fn on_request_bytes(&self, bytes: &[u8]);
+
+    fn on_response_bytes(&self, bytes: &[u8]);
+}
@@ -0,0 +1,570 @@
+//! Amazon Bedrock backend, enabled by the `bedrock` feature.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use reqwest::{header::HeaderValue, Request};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::uri_encode;
+use crate::{
+    api::{message::MessageRequest, stream::StreamEvent},
+    error::{AnthropicError, ApiErrorDetail, ApiErrorResponse, ApiErrorType},
+};
+
+/// Version of the Messages API sent in the body of Bedrock requests.
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+const SERVICE: &str = "bedrock";
+
+/// AWS credentials used to sign Bedrock requests.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Token of temporary credentials, e.g. from an assumed role.
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Reads the credentials from the standard `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// optional `AWS_SESSION_TOKEN` environment variables.
+    pub fn from_env() -> Result<Self, AnthropicError> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| AnthropicError::MissingEnvVar(name.to_string()))
+        };
+        let credentials = Self::new(var("AWS_ACCESS_KEY_ID")?, var("AWS_SECRET_ACCESS_KEY")?);
+        Ok(match std::env::var("AWS_SESSION_TOKEN") {
+            Ok(session_token) => credentials.with_session_token(session_token),
+            Err(_) => credentials,
+        })
+    }
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"[REDACTED]")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+pub(crate) fn base_url(region: &str) -> String {
+    format!("https://bedrock-runtime.{region}.amazonaws.com")
+}
+
+pub(crate) fn message_path(payload: &MessageRequest) -> String {
+    let action = if payload.stream {
+        "invoke-with-response-stream"
+    } else {
+        "invoke"
+    };
+    format!("model/{}/{action}", uri_encode(&payload.model.bedrock_id()))
+}
+
+/// Bedrock takes the model from the URL and the API version and betas from the body.
+pub(crate) fn message_body(payload: &MessageRequest) -> Result<serde_json::Value, AnthropicError> {
+    let mut body = serde_json::to_value(payload).map_err(AnthropicError::Serialize)?;
+    if let Some(body) = body.as_object_mut() {
+        body.remove("model");
+        body.remove("stream");
+        body.insert(
+            "anthropic_version".to_string(),
+            BEDROCK_ANTHROPIC_VERSION.into(),
+        );
+        let betas = payload.required_betas();
+        if !betas.is_empty() {
+            body.insert("anthropic_beta".to_string(), betas.into());
+        }
+    }
+    Ok(body)
+}
+
+/// Signs `request` with AWS Signature Version 4, `amz_date` being the current time formatted by
+/// [`amz_date`].
+pub(crate) fn sign(
+    request: &mut Request,
+    credentials: &AwsCredentials,
+    region: &str,
+    amz_date: &str,
+) -> Result<(), AnthropicError> {
+    sign_service(request, credentials, region, SERVICE, amz_date)
+}
+
+fn sign_service(
+    request: &mut Request,
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    amz_date: &str,
+) -> Result<(), AnthropicError> {
+    let url = request.url();
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    // Services other than S3 expect the path to be encoded once more in the canonical request.
+    let canonical_uri = url
+        .path()
+        .split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| (uri_encode(&name), uri_encode(&value)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut headers = vec![("host", host), ("x-amz-date", amz_date.to_string())];
+    if let Some(session_token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", session_token.clone()));
+    }
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default();
+
+    let canonical_request = format!(
+        "{}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method(),
+        hex(&Sha256::digest(body)),
+    );
+    let date = &amz_date[..8];
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    );
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    );
+
+    for (name, value) in headers.into_iter().skip(1) {
+        let mut value = HeaderValue::from_str(&value)?;
+        value.set_sensitive(name == "x-amz-security-token");
+        request.headers_mut().insert(name, value);
+    }
+    let mut authorization = HeaderValue::from_str(&authorization)?;
+    authorization.set_sensitive(true);
+    request
+        .headers_mut()
+        .insert(reqwest::header::AUTHORIZATION, authorization);
+    Ok(())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Formats `time` as the `YYYYMMDD'T'HHMMSS'Z'` UTC timestamp of the `x-amz-date` header.
+pub(crate) fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// Incremental decoder of the `application/vnd.amazon.eventstream` responses of
+/// `invoke-with-response-stream`.
+///
+/// Each message carries a JSON payload whose `bytes` field is a base64 encoded Messages API
+/// event. Checksums are not verified since the transport is already authenticated.
+pub(crate) struct EventStreamDecoder {
+    buffer: Vec<u8>,
+    raw_frames: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl EventStreamDecoder {
+    pub(crate) fn new(raw_frames: Option<Arc<Mutex<Vec<String>>>>) -> Self {
+        Self {
+            buffer: Vec::new(),
+            raw_frames,
+        }
+    }
+
+    /// Parses the messages completed by `chunk`.
+    pub(crate) fn decode(&mut self, chunk: &[u8]) -> Vec<Result<StreamEvent, AnthropicError>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while self.buffer.len() >= 4 {
+            let total = read_u32(&self.buffer, 0) as usize;
+            if total < 16 {
+                self.buffer.clear();
+                events.push(Err(AnthropicError::InvalidStreamEvent));
+                break;
+            }
+            if self.buffer.len() < total {
+                break;
+            }
+            let message: Vec<u8> = self.buffer.drain(..total).collect();
+            events.push(self.parse_message(&message));
+        }
+        events
+    }
+
+    /// Fails if the body ended in the middle of a message.
+    pub(crate) fn finish(&mut self) -> Vec<Result<StreamEvent, AnthropicError>> {
+        if std::mem::take(&mut self.buffer).is_empty() {
+            Vec::new()
+        } else {
+            vec![Err(AnthropicError::InvalidStreamEvent)]
+        }
+    }
+
+    fn parse_message(&self, message: &[u8]) -> Result<StreamEvent, AnthropicError> {
+        let headers_end = 12 + read_u32(message, 4) as usize;
+        if headers_end > message.len() - 4 {
+            return Err(AnthropicError::InvalidStreamEvent);
+        }
+        let headers = parse_headers(&message[12..headers_end])?;
+        let payload = &message[headers_end..message.len() - 4];
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        if header(":message-type") != Some("event") {
+            #[derive(Deserialize)]
+            struct Exception {
+                message: Option<String>,
+            }
+            let message = serde_json::from_slice::<Exception>(payload)
+                .ok()
+                .and_then(|exception| exception.message)
+                .unwrap_or_default();
+            let error_type = match header(":exception-type") {
+                Some("throttlingException") => ApiErrorType::RateLimit,
+                Some("validationException") => ApiErrorType::InvalidRequest,
+                Some("serviceUnavailableException") => ApiErrorType::Overloaded,
+                _ => ApiErrorType::Unexpected,
+            };
+            return Err(AnthropicError::Api(ApiErrorResponse {
+                error_type: "error".to_string(),
+                error: ApiErrorDetail {
+                    error_type,
+                    message,
+                },
+                retry_after: None,
+                rate_limit: None,
+                request_id: None,
+            }));
+        }
+
+        #[derive(Deserialize)]
+        struct Chunk {
+            bytes: String,
+        }
+        let chunk: Chunk =
+            serde_json::from_slice(payload).map_err(|_| AnthropicError::InvalidStreamEvent)?;
+        let event = STANDARD
+            .decode(chunk.bytes)
+            .map_err(|_| AnthropicError::InvalidStreamEvent)?;
+        let event = String::from_utf8(event).map_err(|_| AnthropicError::InvalidStreamEvent)?;
+        if let Some(raw_frames) = &self.raw_frames {
+            raw_frames
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(event.clone());
+        }
+        event
+            .parse::<StreamEvent>()
+            .map_err(|_| AnthropicError::InvalidStreamEvent)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Splits `bytes` at `mid`, failing if a header is truncated.
+fn split_header(bytes: &[u8], mid: usize) -> Result<(&[u8], &[u8]), AnthropicError> {
+    if mid > bytes.len() {
+        return Err(AnthropicError::InvalidStreamEvent);
+    }
+    Ok(bytes.split_at(mid))
+}
+
+/// Reads the string headers of a message, skipping headers of other types.
+fn parse_headers(mut bytes: &[u8]) -> Result<Vec<(String, String)>, AnthropicError> {
+    let mut headers = Vec::new();
+    while let Some((&name_len, rest)) = bytes.split_first() {
+        let name_len = usize::from(name_len);
+        let (name, rest) = split_header(rest, name_len)?;
+        let (&value_type, rest) = rest
+            .split_first()
+            .ok_or(AnthropicError::InvalidStreamEvent)?;
+        let value_len = match value_type {
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 | 7 => {
+                let (len, _) = split_header(rest, 2)?;
+                2 + usize::from(u16::from_be_bytes([len[0], len[1]]))
+            }
+            _ => return Err(AnthropicError::InvalidStreamEvent),
+        };
+        let (value, rest) = split_header(rest, value_len)?;
+        if value_type == 7 {
+            headers.push((
+                String::from_utf8_lossy(name).into_owned(),
+                String::from_utf8_lossy(&value[2..]).into_owned(),
+            ));
+        }
+        bytes = rest;
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::models::model::Model;
+    use pretty_assertions::assert_eq;
+
+    /// Encodes an event stream message with string headers, leaving checksums zeroed.
+    pub(crate) fn encode_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut encoded_headers = Vec::new();
+        for (name, value) in headers {
+            encoded_headers.push(name.len() as u8);
+            encoded_headers.extend_from_slice(name.as_bytes());
+            encoded_headers.push(7);
+            encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            encoded_headers.extend_from_slice(value.as_bytes());
+        }
+        let total = 16 + encoded_headers.len() + payload.len();
+        let mut message = Vec::with_capacity(total);
+        message.extend_from_slice(&(total as u32).to_be_bytes());
+        message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&encoded_headers);
+        message.extend_from_slice(payload);
+        message.extend_from_slice(&[0; 4]);
+        message
+    }
+
+    /// Encodes a Messages API event as Bedrock streams it.
+    pub(crate) fn encode_event(event: &str) -> Vec<u8> {
+        let payload = serde_json::json!({ "bytes": STANDARD.encode(event) }).to_string();
+        encode_message(
+            &[
+                (":event-type", "chunk"),
+                (":content-type", "application/json"),
+                (":message-type", "event"),
+            ],
+            payload.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn should_sign_request_like_the_sigv4_test_suite() {
+        // "get-vanilla" case of the AWS Signature Version 4 test suite.
+        let mut request = Request::new(
+            reqwest::Method::GET,
+            "https://example.amazonaws.com/".parse().unwrap(),
+        );
+        let credentials =
+            AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+        sign_service(
+            &mut request,
+            &credentials,
+            "us-east-1",
+            "service",
+            "20150830T123600Z",
+        )
+        .unwrap();
+
+        assert_eq!(
+            request.headers()["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+    }
+
+    #[test]
+    fn should_sign_session_token() {
+        let mut request = Request::new(
+            reqwest::Method::POST,
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke"
+                .parse()
+                .unwrap(),
+        );
+        let credentials = AwsCredentials::new("AKIDEXAMPLE", "secret").with_session_token("token");
+        sign(&mut request, &credentials, "us-east-1", "20241015T120000Z").unwrap();
+
+        let authorization = request.headers()["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20241015/us-east-1/bedrock/aws4_request, SignedHeaders=host;x-amz-date;x-amz-security-token, Signature="
+        ));
+        assert_eq!(request.headers()["x-amz-security-token"], "token");
+    }
+
+    #[test]
+    fn should_format_amz_date() {
+        let time = |secs| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        assert_eq!(amz_date(time(1_440_938_160)), "20150830T123600Z");
+        assert_eq!(amz_date(time(1_709_251_199)), "20240229T235959Z");
+        assert_eq!(amz_date(time(0)), "19700101T000000Z");
+    }
+
+    #[test]
+    fn should_build_bedrock_message_body() {
+        let request = MessageRequest::new(Model::Claude3Haiku, 1024, Vec::new())
+            .with_stream(true)
+            .with_beta("custom-beta-2024-01-01");
+        assert_eq!(
+            message_path(&request),
+            "model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke-with-response-stream"
+        );
+
+        let body = message_body(&request).unwrap();
+        assert_eq!(body.get("model"), None);
+        assert_eq!(body.get("stream"), None);
+        assert_eq!(body["anthropic_version"], BEDROCK_ANTHROPIC_VERSION);
+        assert_eq!(
+            body["anthropic_beta"],
+            serde_json::json!(["custom-beta-2024-01-01"])
+        );
+        assert_eq!(body["max_tokens"], 1024);
+    }
+
+    #[test]
+    fn should_decode_event_stream_split_across_chunks() {
+        let mut body = encode_event(r#"{"type":"ping"}"#);
+        body.extend(encode_event(r#"{"type":"message_stop"}"#));
+
+        let mut decoder = EventStreamDecoder::new(None);
+        let mut events = Vec::new();
+        for chunk in body.chunks(5) {
+            events.extend(decoder.decode(chunk).into_iter().map(Result::unwrap));
+        }
+        assert!(decoder.finish().is_empty());
+        assert!(matches!(
+            events.as_slice(),
+            [StreamEvent::Ping, StreamEvent::MessageStop]
+        ));
+    }
+
+    #[test]
+    fn should_decode_event_stream_exception() {
+        let body = encode_message(
+            &[
+                (":exception-type", "throttlingException"),
+                (":message-type", "exception"),
+            ],
+            br#"{"message":"Too many requests"}"#,
+        );
+
+        let mut decoder = EventStreamDecoder::new(None);
+        let events = decoder.decode(&body);
+        assert!(matches!(
+            events.as_slice(),
+            [Err(AnthropicError::Api(ApiErrorResponse { error, .. }))]
+                if error.error_type == ApiErrorType::RateLimit && error.message == "Too many requests"
+        ));
+    }
+
+    #[test]
+    fn should_reject_truncated_headers() {
+        assert_eq!(
+            parse_headers(b"\x05:type\x07\x00\x04json").unwrap(),
+            vec![(":type".to_string(), "json".to_string())]
+        );
+        for truncated in [
+            &b"\x05:ty"[..],
+            b"\x05:type\x07\x00",
+            b"\x05:type\x07\x00\x04js",
+        ] {
+            assert!(matches!(
+                parse_headers(truncated),
+                Err(AnthropicError::InvalidStreamEvent)
+            ));
+        }
+    }
+
+    #[test]
+    fn should_redact_credentials_in_debug() {
+        let credentials = AwsCredentials::new("AKIDEXAMPLE", "secret").with_session_token("token");
+        let debug = format!("{credentials:?}");
+        assert!(debug.contains("AKIDEXAMPLE"));
+        assert!(!debug.contains("secret\""));
+        assert!(!debug.contains("\"token\""));
+    }
+}
@@ -0,0 +1,197 @@
+//! Platforms serving the Messages API, selected with [`Config::with_backend`].
+//!
+//! [`Config::with_backend`]: crate::config::Config::with_backend
+
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
+pub mod vertex;
+
+use std::sync::{Arc, Mutex};
+
+use reqwest::{Request, Url};
+
+use crate::{
+    api::{
+        message::MessageRequest,
+        stream::{BodyDecoder, SseDecoder, StreamEvent},
+    },
+    config::DEFAULT_API_BASE_URL,
+    error::AnthropicError,
+};
+
+#[cfg(feature = "bedrock")]
+use bedrock::{AwsCredentials, EventStreamDecoder};
+use vertex::VertexConfig;
+
+/// Platform the client sends requests to.
+///
+/// Other backends only support the Messages API, other endpoints fail with
+/// [`AnthropicError::UnsupportedByBackend`].
+///
+/// More backends may be added, and `Bedrock` only exists with the `bedrock` feature, so matches
+/// on this enum need a wildcard arm.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub enum Backend {
+    /// The Anthropic API, authenticated with the API key of the config.
+    #[default]
+    Anthropic,
+    /// Amazon Bedrock, authenticated with AWS Signature Version 4.
+    #[cfg(feature = "bedrock")]
+    Bedrock {
+        region: String,
+        credentials: AwsCredentials,
+    },
+    /// Google Vertex AI, authenticated with an OAuth access token.
+    Vertex(VertexConfig),
+}
+
+impl Backend {
+    #[cfg(feature = "bedrock")]
+    pub fn bedrock(region: impl Into<String>, credentials: AwsCredentials) -> Self {
+        Self::Bedrock {
+            region: region.into(),
+            credentials,
+        }
+    }
+
+    pub fn vertex(
+        project_id: impl Into<String>,
+        region: impl Into<String>,
+        access_token: impl Into<String>,
+    ) -> Self {
+        Self::Vertex(VertexConfig::new(project_id, region, access_token))
+    }
+
+    pub(crate) fn is_anthropic(&self) -> bool {
+        matches!(self, Self::Anthropic)
+    }
+
+    /// Default endpoint of the backend.
+    pub(crate) fn base_url(&self) -> String {
+        match self {
+            Self::Anthropic => DEFAULT_API_BASE_URL.to_string(),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { region, .. } => bedrock::base_url(region),
+            Self::Vertex(config) => vertex::base_url(&config.region),
+        }
+    }
+
+    pub(crate) fn message_url(
+        &self,
+        base_url: &Url,
+        payload: &MessageRequest,
+    ) -> Result<Url, AnthropicError> {
+        let path = match self {
+            Self::Anthropic => "messages".to_string(),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { .. } => bedrock::message_path(payload),
+            Self::Vertex(config) => vertex::message_path(config, payload),
+        };
+        base_url
+            .join(&path)
+            .map_err(|err| AnthropicError::UrlParse(err.to_string()))
+    }
+
+    /// Body of a messages request, in the shape expected by the backend.
+    pub(crate) fn message_body(
+        &self,
+        payload: &MessageRequest,
+    ) -> Result<serde_json::Value, AnthropicError> {
+        match self {
+            Self::Anthropic => serde_json::to_value(payload).map_err(AnthropicError::Serialize),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { .. } => bedrock::message_body(payload),
+            Self::Vertex(_) => vertex::message_body(payload),
+        }
+    }
+
+    /// Adds the authentication the backend requires to `request`, once its body is final.
+    pub(crate) fn authorize(&self, request: &mut Request) -> Result<(), AnthropicError> {
+        match self {
+            // The API key is part of the default headers.
+            Self::Anthropic => Ok(()),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock {
+                region,
+                credentials,
+            } => bedrock::sign(
+                request,
+                credentials,
+                region,
+                &bedrock::amz_date(std::time::SystemTime::now()),
+            ),
+            Self::Vertex(config) => vertex::authorize(request, config),
+        }
+    }
+
+    /// Whether betas are sent as the `anthropic-beta` header rather than in the request body.
+    pub(crate) fn sends_beta_header(&self) -> bool {
+        match self {
+            Self::Anthropic | Self::Vertex(_) => true,
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { .. } => false,
+        }
+    }
+
+    /// Media type of streamed responses, sent as the `accept` header.
+    pub(crate) fn stream_content_type(&self) -> &'static str {
+        match self {
+            Self::Anthropic | Self::Vertex(_) => "text/event-stream",
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { .. } => "application/vnd.amazon.eventstream",
+        }
+    }
+
+    pub(crate) fn stream_decoder(
+        &self,
+        raw_frames: Option<Arc<Mutex<Vec<String>>>>,
+    ) -> StreamDecoder {
+        match self {
+            Self::Anthropic | Self::Vertex(_) => StreamDecoder::Sse(SseDecoder::new(raw_frames)),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock { .. } => StreamDecoder::EventStream(EventStreamDecoder::new(raw_frames)),
+        }
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters, as required by SigV4 and to keep
+/// user-provided values within a single path segment.
+pub(crate) fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Decoder of the streamed responses of a backend.
+pub(crate) enum StreamDecoder {
+    Sse(SseDecoder),
+    #[cfg(feature = "bedrock")]
+    EventStream(EventStreamDecoder),
+}
+
+impl BodyDecoder for StreamDecoder {
+    type Item = StreamEvent;
+
+    fn decode(&mut self, chunk: &[u8]) -> Vec<Result<StreamEvent, AnthropicError>> {
+        match self {
+            Self::Sse(decoder) => decoder.decode(chunk),
+            #[cfg(feature = "bedrock")]
+            Self::EventStream(decoder) => decoder.decode(chunk),
+        }
+    }
+
+    fn finish(&mut self) -> Vec<Result<StreamEvent, AnthropicError>> {
+        match self {
+            Self::Sse(decoder) => decoder.finish(),
+            #[cfg(feature = "bedrock")]
+            Self::EventStream(decoder) => decoder.finish(),
+        }
+    }
+}
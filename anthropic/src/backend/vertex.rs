@@ -0,0 +1,173 @@
+//! Google Vertex AI backend.
+
+use std::{fmt, sync::Arc};
+
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Request,
+};
+
+use super::uri_encode;
+use crate::{api::message::MessageRequest, error::AnthropicError};
+
+/// Version of the Messages API sent in the body of Vertex AI requests.
+const VERTEX_ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+
+/// Source of the OAuth access token sent with every Vertex AI request.
+///
+/// Tokens from Application Default Credentials expire after an hour, so providers are called
+/// before each request and should return a cached token until it is about to expire. Closures
+/// returning a token implement this trait.
+pub trait AccessTokenProvider: Send + Sync {
+    fn access_token(&self) -> Result<String, AnthropicError>;
+}
+
+impl<F> AccessTokenProvider for F
+where
+    F: Fn() -> Result<String, AnthropicError> + Send + Sync,
+{
+    fn access_token(&self) -> Result<String, AnthropicError> {
+        self()
+    }
+}
+
+/// A fixed token, e.g. from `gcloud auth application-default print-access-token`.
+struct StaticToken(String);
+
+impl AccessTokenProvider for StaticToken {
+    fn access_token(&self) -> Result<String, AnthropicError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Google Cloud project and credentials used to call Claude on Vertex AI.
+#[derive(Clone)]
+pub struct VertexConfig {
+    pub project_id: String,
+    /// Region of the endpoint, e.g. `us-east5`, or `global`.
+    pub region: String,
+    pub access_token: Arc<dyn AccessTokenProvider>,
+}
+
+impl VertexConfig {
+    /// Authenticates every request with the fixed `access_token`.
+    ///
+    /// The token is not refreshed, use [`VertexConfig::with_token_provider`] for long-lived
+    /// clients.
+    pub fn new(
+        project_id: impl Into<String>,
+        region: impl Into<String>,
+        access_token: impl Into<String>,
+    ) -> Self {
+        Self::with_token_provider(project_id, region, StaticToken(access_token.into()))
+    }
+
+    /// Authenticates every request with a token obtained from `provider`.
+    pub fn with_token_provider(
+        project_id: impl Into<String>,
+        region: impl Into<String>,
+        provider: impl AccessTokenProvider + 'static,
+    ) -> Self {
+        Self {
+            project_id: project_id.into(),
+            region: region.into(),
+            access_token: Arc::new(provider),
+        }
+    }
+}
+
+impl fmt::Debug for VertexConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VertexConfig")
+            .field("project_id", &self.project_id)
+            .field("region", &self.region)
+            .field("access_token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+pub(crate) fn base_url(region: &str) -> String {
+    match region {
+        "global" => "https://aiplatform.googleapis.com".to_string(),
+        region => format!("https://{region}-aiplatform.googleapis.com"),
+    }
+}
+
+pub(crate) fn message_path(config: &VertexConfig, payload: &MessageRequest) -> String {
+    let method = if payload.stream {
+        "streamRawPredict"
+    } else {
+        "rawPredict"
+    };
+    format!(
+        "v1/projects/{}/locations/{}/publishers/anthropic/models/{}:{method}",
+        uri_encode(&config.project_id),
+        uri_encode(&config.region),
+        payload.model.vertex_id()
+    )
+}
+
+/// Vertex AI takes the model from the URL and the API version from the body.
+pub(crate) fn message_body(payload: &MessageRequest) -> Result<serde_json::Value, AnthropicError> {
+    let mut body = serde_json::to_value(payload).map_err(AnthropicError::Serialize)?;
+    if let Some(body) = body.as_object_mut() {
+        body.remove("model");
+        body.insert(
+            "anthropic_version".to_string(),
+            VERTEX_ANTHROPIC_VERSION.into(),
+        );
+    }
+    Ok(body)
+}
+
+pub(crate) fn authorize(
+    request: &mut Request,
+    config: &VertexConfig,
+) -> Result<(), AnthropicError> {
+    let access_token = config.access_token.access_token()?;
+    let mut authorization = HeaderValue::from_str(&format!("Bearer {access_token}"))?;
+    authorization.set_sensitive(true);
+    request.headers_mut().insert(AUTHORIZATION, authorization);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::model::Model;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_build_vertex_message_path() {
+        let config = VertexConfig::new("my-project", "us-east5", "token");
+        let request = MessageRequest::new(Model::Claude35SonnetLatest, 1024, Vec::new());
+        assert_eq!(
+            message_path(&config, &request),
+            "v1/projects/my-project/locations/us-east5/publishers/anthropic/models/claude-3-5-sonnet-v2@20241022:rawPredict"
+        );
+        assert_eq!(
+            message_path(&config, &request.with_stream(true)),
+            "v1/projects/my-project/locations/us-east5/publishers/anthropic/models/claude-3-5-sonnet-v2@20241022:streamRawPredict"
+        );
+        assert_eq!(base_url("global"), "https://aiplatform.googleapis.com");
+    }
+
+    #[test]
+    fn should_escape_vertex_path_segments() {
+        let config = VertexConfig::new("my-project/../other?", "us-east5", "token");
+        let request = MessageRequest::new(Model::Claude35SonnetLatest, 1024, Vec::new());
+        assert_eq!(
+            message_path(&config, &request),
+            "v1/projects/my-project%2F..%2Fother%3F/locations/us-east5/publishers/anthropic/models/claude-3-5-sonnet-v2@20241022:rawPredict"
+        );
+    }
+
+    #[test]
+    fn should_build_vertex_message_body() {
+        let request = MessageRequest::new(Model::Claude3Haiku, 1024, Vec::new()).with_stream(true);
+        let body = message_body(&request).unwrap();
+        assert_eq!(body.get("model"), None);
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["anthropic_version"], VERTEX_ANTHROPIC_VERSION);
+    }
+}
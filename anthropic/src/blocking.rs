@@ -0,0 +1,136 @@
+//! Synchronous client, enabled by the `blocking` feature.
+//!
+//! Requests go through the async [`Client`] driven by a runtime owned by the blocking client,
+//! the same way `reqwest::blocking` works, so retries, backends and every other setting behave
+//! identically. Blocking calls panic when made from within an async runtime.
+//!
+//! [`Client`]: crate::client::Client
+
+use futures_util::StreamExt;
+use tokio::runtime::Runtime;
+
+use crate::{
+    api::{
+        message::{MessageRequest, MessageResponse, TokenCount},
+        stream::{MessageStream, StreamEvent},
+    },
+    client,
+    config::Config,
+    error::AnthropicError,
+};
+
+pub struct Client {
+    inner: client::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    pub fn new(config: Config) -> Result<Self, AnthropicError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        // The HTTP client must be created within the runtime driving its connections.
+        let inner = runtime.block_on(async { client::Client::new(config) })?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// The underlying async client, e.g. to read its settings.
+    pub fn inner(&self) -> &client::Client {
+        &self.inner
+    }
+
+    /// Sends `payload` and waits for the complete response, see
+    /// [`client::Client::create_message`].
+    pub fn create_message(
+        &self,
+        payload: MessageRequest,
+    ) -> Result<MessageResponse, AnthropicError> {
+        self.runtime.block_on(self.inner.create_message(payload))
+    }
+
+    /// Sends `request` and returns an iterator over the events of the streamed response, see
+    /// [`client::Client::stream_message`].
+    pub fn stream_message(
+        &self,
+        request: MessageRequest,
+    ) -> Result<MessageEvents<'_>, AnthropicError> {
+        let stream = self.runtime.block_on(self.inner.stream_message(request))?;
+        Ok(MessageEvents {
+            runtime: &self.runtime,
+            stream,
+        })
+    }
+
+    /// Counts the input tokens of `request`, see [`client::Client::count_tokens`].
+    pub fn count_tokens(&self, request: MessageRequest) -> Result<TokenCount, AnthropicError> {
+        self.runtime.block_on(self.inner.count_tokens(request))
+    }
+}
+
+/// Iterator over the events of a streamed response, returned by [`Client::stream_message`].
+pub struct MessageEvents<'a> {
+    runtime: &'a Runtime,
+    stream: MessageStream,
+}
+
+impl MessageEvents<'_> {
+    /// The underlying stream, e.g. to read its usage or request id.
+    pub fn stream(&self) -> &MessageStream {
+        &self.stream
+    }
+}
+
+impl Iterator for MessageEvents<'_> {
+    type Item = Result<StreamEvent, AnthropicError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::message::Message, models::model::Model};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_create_and_stream_messages_synchronously() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "stream": false }),
+            ))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create();
+        let message_start = serde_json::json!({
+            "type": "message_start",
+            "message": MessageResponse::mock_text(""),
+        });
+        server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "stream": true }),
+            ))
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!(
+                "event: message_start\ndata: {message_start}\n\nevent: message_stop\ndata: {{\"type\":\"message_stop\"}}\n\n"
+            ))
+            .create();
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let request = MessageRequest::new(Model::Claude3Haiku, 256, vec![Message::user("Hello")]);
+        let response = client.create_message(request.clone()).unwrap();
+        assert_eq!(response.first_text(), Some("Hi"));
+
+        let events: Vec<_> = client
+            .stream_message(request)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], StreamEvent::MessageStop);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+use crate::error::{AnthropicError, ApiErrorType};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Thresholds of the circuit breaker shared by all requests of a [`Client`].
+///
+/// [`Client`]: crate::client::Client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive rate limit or overloaded errors after which the circuit opens.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before letting a request through again.
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerConfig {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+        }
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+/// Fails requests fast after sustained rate limiting so concurrent retries don't pile up.
+///
+/// Once the cooldown has elapsed the circuit is half-open: requests go through, a success closes
+/// the circuit and another rate limit or overloaded error opens it again right away.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    /// Returns `CircuitOpen` while the circuit is open.
+    pub(crate) fn check(&self) -> Result<(), AnthropicError> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.config.cooldown => {
+                Err(AnthropicError::CircuitOpen)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the outcome of a request.
+    pub(crate) fn record<T>(&self, result: &Result<T, AnthropicError>) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        match result {
+            Err(AnthropicError::Api(response))
+                if matches!(
+                    response.error.error_type,
+                    ApiErrorType::RateLimit | ApiErrorType::Overloaded
+                ) =>
+            {
+                state.consecutive_failures += 1;
+                if state.consecutive_failures >= self.config.failure_threshold {
+                    state.opened_at = Some(Instant::now());
+                }
+            }
+            Ok(_) => *state = CircuitState::default(),
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ApiErrorDetail, ApiErrorResponse};
+
+    fn rate_limited() -> Result<(), AnthropicError> {
+        Err(AnthropicError::Api(ApiErrorResponse {
+            error_type: "error".to_string(),
+            error: ApiErrorDetail {
+                error_type: ApiErrorType::RateLimit,
+                message: "Rate limited".to_string(),
+            },
+            retry_after: None,
+            rate_limit: None,
+            request_id: None,
+        }))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_open_after_threshold_and_half_open_after_cooldown() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig::new(2, Duration::from_secs(10)));
+
+        breaker.record(&rate_limited());
+        assert!(breaker.check().is_ok());
+        breaker.record(&rate_limited());
+        assert!(matches!(breaker.check(), Err(AnthropicError::CircuitOpen)));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(breaker.check().is_ok());
+
+        breaker.record(&rate_limited());
+        assert!(matches!(breaker.check(), Err(AnthropicError::CircuitOpen)));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        breaker.record(&Ok(()));
+        breaker.record(&rate_limited());
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn should_ignore_other_errors() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig::new(1, Duration::from_secs(10)));
+        breaker.record::<()>(&Err(AnthropicError::PayloadTooLarge(1024)));
+        assert!(breaker.check().is_ok());
+    }
+}
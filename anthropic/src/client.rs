@@ -1,59 +1,123 @@
 use core::fmt;
 use futures_util::{stream, Stream, StreamExt};
 use reqwest::{
-    header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE},
-    Method, RequestBuilder, Url,
+    header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE, RETRY_AFTER},
+    Method, RequestBuilder, Response, StatusCode, Url, Version,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use serde::{Deserialize, Serialize};
-use std::str::FromStr;
 
 use crate::{
     api::{
-        message::{MessageRequest, MessageResponse},
-        stream::StreamEvent,
+        batch::{
+            BatchList, BatchRequest, BatchResult, JsonLinesDecoder, ListBatchesParams, MessageBatch,
+        },
+        message::{CountTokensRequest, Message, MessageRequest, MessageResponse, TokenCount},
+        stream::{BodyDecoder, MessageStream, StreamEvent},
+    },
+    audit::AuditSink,
+    backend::Backend,
+    circuit_breaker::CircuitBreaker,
+    config::{Config, SecretKey},
+    error::{AnthropicError, ApiErrorDetail, ApiErrorResponse, ApiErrorType},
+    models::{
+        list::{ListModelsParams, ModelInfo, ModelList},
+        model::{CostEstimate, Model, ModelPricing},
     },
-    config::Config,
-    error::{AnthropicError, ApiErrorResponse},
+    rate_limit::RateLimit,
+    retry::RetryConfig,
 };
 
 pub struct Client {
-    api_key: String,
+    api_key: SecretKey,
     api_version: ApiVersion,
     anthropic_version: AnthropicVersion,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    backend: Backend,
     base_url: Url,
+    betas: Vec<String>,
+    circuit_breaker: Option<CircuitBreaker>,
+    default_model: Option<Model>,
+    default_query: Vec<(String, String)>,
+    headers: HeaderMap,
     http_client: reqwest::Client,
+    max_image_size: usize,
+    max_request_bytes: usize,
+    max_output_chars: Option<usize>,
+    model_fallbacks: HashMap<Model, Model>,
+    model_pricing: HashMap<Model, ModelPricing>,
+    raw_stream_capture: bool,
+    retry: Option<RetryConfig>,
 }
 
 impl Client {
     pub fn new(config: Config) -> Result<Self, AnthropicError> {
         let mut headers = HeaderMap::new();
-        headers.insert(
-            "x-api-key",
-            HeaderValue::from_str(config.api_key.as_str())
-                .map_err(AnthropicError::InvalidHeaderValue)?,
-        );
-        headers.insert(
-            "anthropic-version",
-            HeaderValue::from_str(&config.anthropic_version.to_string())
-                .map_err(AnthropicError::InvalidHeaderValue)?,
-        );
+        if config.backend.is_anthropic() {
+            let mut api_key = HeaderValue::from_str(config.api_key())
+                .map_err(AnthropicError::InvalidHeaderValue)?;
+            api_key.set_sensitive(true);
+            headers.insert("x-api-key", api_key);
+            headers.insert(
+                "anthropic-version",
+                HeaderValue::from_str(&config.anthropic_version.to_string())
+                    .map_err(AnthropicError::InvalidHeaderValue)?,
+            );
+        }
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if config.browser_access {
+            headers.insert(
+                "anthropic-dangerous-direct-browser-access",
+                HeaderValue::from_static("true"),
+            );
+        }
 
-        let http_client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
+        // API headers are attached per request rather than as client defaults so the
+        // underlying HTTP client can be reused for third-party downloads without leaking the key.
+        let mut http_client = reqwest::Client::builder()
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout);
+        if let Some(timeout) = config.timeout {
+            http_client = http_client.timeout(timeout);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            http_client = http_client.connect_timeout(timeout);
+        }
+        let http_client = http_client.build()?;
 
-        let base_url = Url::parse(&config.base_url)
-            .map_err(|err| AnthropicError::UrlParse(err.to_string()))?
-            .join(format!("{}/", config.api_version).as_str())
-            .map_err(|err| AnthropicError::UrlParse(err.to_string()))?;
+        let mut base_url = parse_base_url(&config.base_url)?;
+        // Other backends version their API in the request path or body.
+        if config.backend.is_anthropic() {
+            base_url = base_url
+                .join(format!("{}/", config.api_version).as_str())
+                .map_err(|err| AnthropicError::UrlParse(err.to_string()))?;
+        }
 
         Ok(Self {
             anthropic_version: config.anthropic_version,
             api_key: config.api_key,
             api_version: config.api_version,
+            audit_sink: config.audit_sink,
+            backend: config.backend,
             base_url,
+            betas: config.betas,
+            circuit_breaker: config.circuit_breaker.map(CircuitBreaker::new),
+            default_model: config.default_model,
+            default_query: config.default_query,
+            headers,
             http_client,
+            max_image_size: config.max_image_size,
+            max_request_bytes: config.max_request_bytes,
+            max_output_chars: config.max_output_chars,
+            model_fallbacks: config.model_fallbacks,
+            model_pricing: config.model_pricing,
+            raw_stream_capture: config.raw_stream_capture,
+            retry: config.retry,
         })
     }
 
@@ -62,7 +126,7 @@ impl Client {
     }
 
     pub fn api_key(&self) -> &str {
-        self.api_key.as_str()
+        self.api_key.expose()
     }
 
     pub fn api_version(&self) -> &ApiVersion {
@@ -73,121 +137,758 @@ impl Client {
         self.base_url.as_str()
     }
 
+    pub fn default_model(&self) -> Option<&Model> {
+        self.default_model.as_ref()
+    }
+
+    /// Resolves the model a request is sent with.
+    ///
+    /// An explicitly chosen model always wins. A request left at [`Model::default`] is treated as
+    /// unset and falls back to the client default model, if any.
+    pub fn resolve_model(&self, model: Model) -> Model {
+        match &self.default_model {
+            Some(default_model) if model == Model::default() => default_model.clone(),
+            _ => model,
+        }
+    }
+
+    pub fn max_image_size(&self) -> usize {
+        self.max_image_size
+    }
+
+    pub(crate) fn http_client(&self) -> &reqwest::Client {
+        &self.http_client
+    }
+
+    /// Ensures the configured `anthropic-version` is recent enough for `model`.
+    ///
+    /// Always passes on other backends, which never send the `anthropic-version` header.
+    pub fn check_model_version(&self, model: &Model) -> Result<(), AnthropicError> {
+        if !self.backend.is_anthropic() {
+            return Ok(());
+        }
+        let minimum = model.min_anthropic_version();
+        if self.anthropic_version.supports(&minimum) {
+            Ok(())
+        } else {
+            Err(AnthropicError::UnsupportedAnthropicVersion(
+                model.clone(),
+                minimum,
+            ))
+        }
+    }
+
+    /// Starts a request to an endpoint of the Anthropic API, with the betas of the config.
     fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, AnthropicError> {
-        let url = self
-            .base_url
+        self.api_request(method, self.endpoint_url(path)?)
+    }
+
+    /// Same as [`Client::request`] for a URL built with [`Client::endpoint_url`].
+    fn api_request(&self, method: Method, url: Url) -> Result<RequestBuilder, AnthropicError> {
+        let request = self.request_url(method, url)?;
+        if self.betas.is_empty() {
+            return Ok(request);
+        }
+        Ok(request.header("anthropic-beta", self.betas.join(",")))
+    }
+
+    /// URL of an endpoint of the Anthropic API, unavailable on other backends.
+    fn endpoint_url(&self, path: &str) -> Result<Url, AnthropicError> {
+        if !self.backend.is_anthropic() {
+            return Err(AnthropicError::UnsupportedByBackend(path.to_string()));
+        }
+        self.base_url
             .join(path)
-            .map_err(|err| AnthropicError::UrlParse(err.to_string()))?;
-        Ok(self.http_client.request(method, url))
+            .map_err(|err| AnthropicError::UrlParse(err.to_string()))
+    }
+
+    /// URL of the batch `id`, or of one of its sub-resources with `segments`.
+    ///
+    /// The id is percent-encoded so it always stays a single path segment.
+    fn batch_url(&self, id: &str, segments: &[&str]) -> Result<Url, AnthropicError> {
+        let mut url = self.endpoint_url("messages/batches")?;
+        url.path_segments_mut()
+            .map_err(|_| AnthropicError::UrlParse("base URL cannot have a path".to_string()))?
+            .push(id)
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn request_url(&self, method: Method, url: Url) -> Result<RequestBuilder, AnthropicError> {
+        let request = self
+            .http_client
+            .request(method, url)
+            .headers(self.headers.clone());
+        if self.default_query.is_empty() {
+            return Ok(request);
+        }
+        Ok(request.query(&self.default_query))
+    }
+
+    /// Sends a messages request, within a span when the `tracing` feature is enabled.
+    ///
+    /// The span only carries the model and the `metadata.user_id`, never message content.
+    async fn send_message(&self, payload: &MessageRequest) -> Result<Response, AnthropicError> {
+        let url = self.backend.message_url(&self.base_url, payload)?;
+        let mut request = self.request_url(Method::POST, url)?;
+        if payload.stream {
+            request = request.header(ACCEPT, self.backend.stream_content_type());
+        }
+        let betas = payload.required_betas();
+        if !betas.is_empty() && self.backend.sends_beta_header() {
+            request = request.header("anthropic-beta", betas.join(","));
+        }
+        let send = async {
+            if self.backend.is_anthropic() {
+                self.send(request, payload).await
+            } else {
+                self.send(request, &self.backend.message_body(payload)?)
+                    .await
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let send = tracing::Instrument::instrument(
+            send,
+            tracing::info_span!(
+                "anthropic.messages",
+                model = %payload.model,
+                user_id = payload
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.user_id.as_deref()),
+            ),
+        );
+        send.await
+    }
+
+    /// Sends `payload` as the JSON body of `request` and turns unsuccessful responses into errors.
+    ///
+    /// Payloads larger than the configured `max_request_bytes` are rejected without being sent.
+    async fn send(
+        &self,
+        request: RequestBuilder,
+        payload: &impl Serialize,
+    ) -> Result<Response, AnthropicError> {
+        let body = serde_json::to_vec(payload).map_err(AnthropicError::Serialize)?;
+        let size = body.len();
+        if size > self.max_request_bytes {
+            return Err(AnthropicError::PayloadTooLarge(size));
+        }
+        if let Some(audit_sink) = &self.audit_sink {
+            audit_sink.on_request_bytes(&body);
+        }
+        self.execute(request.body(body), size).await
+    }
+
+    /// Sends `request`, retrying transient failures according to the retry configuration.
+    ///
+    /// Only the initial response is retried, so a stream that already yielded events is never
+    /// sent again.
+    async fn execute(
+        &self,
+        request: RequestBuilder,
+        size: usize,
+    ) -> Result<Response, AnthropicError> {
+        let mut attempt = 0;
+        loop {
+            let retry = self
+                .retry
+                .as_ref()
+                .filter(|retry| attempt < retry.max_retries);
+            let (Some(retry), Some(next)) = (retry, request.try_clone()) else {
+                return self.execute_once(request, size).await;
+            };
+            match self.execute_once(next, size).await {
+                Err(err) if err.is_retryable() => {
+                    let delay = match &err {
+                        AnthropicError::Api(response) => response.retry_after,
+                        _ => None,
+                    };
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(attempt, error = %err, "retrying failed request");
+                    match delay {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => retry.wait(attempt).await,
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends `request` through the circuit breaker and turns unsuccessful responses into errors.
+    ///
+    /// `size` is the size of the request body, reported if the API rejects it as too large.
+    async fn execute_once(
+        &self,
+        request: RequestBuilder,
+        size: usize,
+    ) -> Result<Response, AnthropicError> {
+        let Some(circuit_breaker) = &self.circuit_breaker else {
+            return self.execute_unguarded(request, size).await;
+        };
+        circuit_breaker.check()?;
+        let result = self.execute_unguarded(request, size).await;
+        circuit_breaker.record(&result);
+        result
+    }
+
+    async fn execute_unguarded(
+        &self,
+        request: RequestBuilder,
+        size: usize,
+    ) -> Result<Response, AnthropicError> {
+        let mut request = request.build()?;
+        self.backend.authorize(&mut request)?;
+        let response = self.http_client.execute(request).await?;
+
+        if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Err(AnthropicError::PayloadTooLarge(size));
+        }
+
+        if !response.status().is_success() {
+            let headers = response.headers();
+            let retry_after = parse_retry_after(headers);
+            let rate_limit = RateLimit::from_headers(headers).map(Box::new);
+            let request_id = parse_request_id(headers);
+            let status = response.status();
+            let error = self.read_body(response).await?;
+            let api_error = match serde_json::from_slice::<ApiErrorResponse>(&error) {
+                Ok(api_error) => api_error,
+                // Other backends report errors in their own format.
+                Err(_) if !self.backend.is_anthropic() => backend_error(status, &error),
+                Err(err) => return Err(AnthropicError::JsonDeserialize(err)),
+            };
+            return Err(AnthropicError::Api(ApiErrorResponse {
+                retry_after,
+                rate_limit,
+                request_id,
+                ..api_error
+            }));
+        }
+
+        Ok(response)
+    }
+
+    /// Reads the whole body of `response`, reporting it to the audit sink.
+    async fn read_body(&self, response: Response) -> Result<bytes::Bytes, AnthropicError> {
+        let body = response.bytes().await?;
+        if let Some(audit_sink) = &self.audit_sink {
+            audit_sink.on_response_bytes(&body);
+        }
+        Ok(body)
+    }
+
+    async fn read_json<T: DeserializeOwned>(
+        &self,
+        response: Response,
+    ) -> Result<T, AnthropicError> {
+        Ok(serde_json::from_slice(&self.read_body(response).await?)?)
+    }
+
+    /// Applies the client-side transforms and validations to `request`, without sending it.
+    ///
+    /// The result is exactly what [`Client::create_message`] or [`Client::stream_message`] send.
+    pub fn finalize_request(
+        &self,
+        request: &MessageRequest,
+    ) -> Result<MessageRequest, AnthropicError> {
+        let mut request = request.clone();
+        request.model = self.resolve_model(request.model);
+        if !self.betas.is_empty() {
+            let betas = std::mem::take(&mut request.betas);
+            request.betas = self.betas.iter().cloned().chain(betas).collect();
+        }
+        self.check_model_version(&request.model)?;
+        request.validate()?;
+        Ok(request)
     }
 
+    /// Sends `payload` and waits for the complete response.
+    ///
+    /// Requests with `stream` enabled are rejected, use [`Client::stream_message`] instead.
     pub async fn create_message(
         &self,
         payload: MessageRequest,
     ) -> Result<MessageResponse, AnthropicError> {
-        let response = self
-            .request(Method::POST, "messages")?
-            .json(&payload)
-            .send()
-            .await?;
+        let (response, _) = self.create_message_with_meta(payload).await?;
+        Ok(response)
+    }
 
-        if !response.status().is_success() {
-            let error = response.text().await?;
-            match serde_json::from_str::<ApiErrorResponse>(&error) {
-                Ok(api_error) => return Err(AnthropicError::Api(api_error)),
-                Err(err) => return Err(AnthropicError::JsonDeserialize(err)),
+    /// Same as [`Client::create_message`] but also returns transport details of the response.
+    pub async fn create_message_with_meta(
+        &self,
+        payload: MessageRequest,
+    ) -> Result<(MessageResponse, ResponseMeta), AnthropicError> {
+        if payload.stream {
+            return Err(AnthropicError::InvalidParameter {
+                field: "stream",
+                message: "streaming requests must be sent with `stream_message`".to_string(),
+            });
+        }
+        let mut payload = self.finalize_request(&payload)?;
+        match self.send_create_message(&payload).await {
+            Err(AnthropicError::Api(err)) if is_model_not_found(&err, &payload.model) => {
+                let Some(fallback) = self.model_fallbacks.get(&payload.model) else {
+                    return Err(AnthropicError::Api(err));
+                };
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    model = %payload.model,
+                    fallback = %fallback,
+                    "model not found, retrying with fallback"
+                );
+                payload.model = fallback.clone();
+                self.check_model_version(&payload.model)?;
+                self.send_create_message(&payload).await
             }
+            result => result,
         }
+    }
+
+    async fn send_create_message(
+        &self,
+        payload: &MessageRequest,
+    ) -> Result<(MessageResponse, ResponseMeta), AnthropicError> {
+        let response = self.send_message(payload).await?;
+        let meta = ResponseMeta {
+            http_version: response.version(),
+            request_id: parse_request_id(response.headers()),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(http_version = ?meta.http_version, "received message response");
+
+        // Some proxies and gateways report errors with a successful status.
+        let body = self.read_body(response).await?;
+        if let Ok(api_error) = serde_json::from_slice::<ApiErrorResponse>(&body) {
+            if api_error.error_type == "error" {
+                return Err(AnthropicError::Api(api_error));
+            }
+        }
+
+        let message = serde_json::from_slice::<MessageResponse>(&body)?;
+        Ok((message, meta))
+    }
 
-        response
-            .json::<MessageResponse>()
+    /// Sends `messages` with the given model and token limit, leaving other parameters unset.
+    pub async fn chat(
+        &self,
+        model: Model,
+        max_tokens: u32,
+        messages: Vec<Message>,
+    ) -> Result<MessageResponse, AnthropicError> {
+        self.create_message(MessageRequest::new(model, max_tokens, messages))
             .await
-            .map_err(AnthropicError::from)
     }
 
-    pub async fn stream_message(
+    /// Counts the input tokens of `request` without sending it to the model, e.g. to stay under
+    /// the context window of long conversations.
+    ///
+    /// Only the fields contributing to the input are sent, so `max_tokens` and the sampling
+    /// parameters are ignored. The betas required by the request, e.g. for PDF documents, are
+    /// sent along with the token counting beta.
+    pub async fn count_tokens(
         &self,
         request: MessageRequest,
-    ) -> Result<impl Stream<Item = Result<StreamEvent, AnthropicError>>, AnthropicError> {
+    ) -> Result<TokenCount, AnthropicError> {
+        let request = self.finalize_request(&request)?;
+        let mut betas = request.required_betas();
+        if !betas.iter().any(|beta| beta == TOKEN_COUNTING_BETA) {
+            betas.push(TOKEN_COUNTING_BETA.to_string());
+        }
         let response = self
-            .request(Method::POST, "messages")?
-            .header(ACCEPT, "text/event-stream")
-            .json(&request)
-            .send()
+            .send(
+                self.request_url(Method::POST, self.endpoint_url("messages/count_tokens")?)?
+                    .header("anthropic-beta", betas.join(",")),
+                &CountTokensRequest::from(&request),
+            )
             .await?;
+        self.read_json(response).await
+    }
 
-        if !response.status().is_success() {
-            let error = response.text().await?;
-            match serde_json::from_str::<ApiErrorResponse>(&error) {
-                Ok(api_error) => return Err(AnthropicError::Api(api_error)),
-                Err(err) => return Err(AnthropicError::JsonDeserialize(err)),
+    /// Estimates the cost of `request` before sending it, e.g. to ask users for confirmation.
+    ///
+    /// Input tokens are estimated locally with [`MessageRequest::estimate_input_tokens`] and the
+    /// output is assumed to reach `max_tokens`. Prices configured with
+    /// [`Config::with_model_pricing`] take precedence over [`Model::pricing`].
+    pub fn estimate_cost(&self, request: &MessageRequest) -> Result<CostEstimate, AnthropicError> {
+        let request = self.finalize_request(request)?;
+        let pricing = self
+            .model_pricing
+            .get(&request.model)
+            .copied()
+            .or_else(|| request.model.pricing())
+            .ok_or_else(|| AnthropicError::ModelNotSupported(request.model.to_string()))?;
+        Ok(pricing.estimate(request.estimate_input_tokens(), request.max_tokens))
+    }
+
+    /// Lists all the models available to the account, most recently released first.
+    ///
+    /// Follows the pages of [`Client::list_models_page`] until the last one.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, AnthropicError> {
+        let mut models = Vec::new();
+        let mut params = ListModelsParams::new().with_limit(1000);
+        loop {
+            let page = self.list_models_page(&params).await?;
+            models.extend(page.data);
+            match page.last_id {
+                Some(last_id) if page.has_more => params = params.with_after_id(last_id),
+                _ => return Ok(models),
             }
         }
+    }
+
+    /// Lists a single page of the models available to the account.
+    pub async fn list_models_page(
+        &self,
+        params: &ListModelsParams,
+    ) -> Result<ModelList, AnthropicError> {
+        let request = self.request(Method::GET, "models")?.query(params);
+        let response = self.execute(request, 0).await?;
+        self.read_json(response).await
+    }
+
+    /// Submits `requests` for asynchronous processing, at a lower cost than individual requests.
+    ///
+    /// Each request is finalized as by [`Client::finalize_request`], and streaming requests are
+    /// rejected. The batch is sent with the betas required by any of its requests.
+    pub async fn create_batch(
+        &self,
+        requests: Vec<BatchRequest>,
+    ) -> Result<MessageBatch, AnthropicError> {
+        #[derive(Serialize)]
+        struct CreateBatch {
+            requests: Vec<BatchRequest>,
+        }
 
-        Ok(response.bytes_stream().flat_map(move |chunk| match chunk {
-            Ok(bytes) => {
-                let events = Self::parse_stream_chunk(&bytes);
-                stream::iter(events)
+        let requests = requests
+            .into_iter()
+            .map(|request| {
+                if request.params.stream {
+                    return Err(AnthropicError::InvalidParameter {
+                        field: "stream",
+                        message: "batch requests cannot be streamed".to_string(),
+                    });
+                }
+                Ok(BatchRequest {
+                    params: self.finalize_request(&request.params)?,
+                    ..request
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Request betas are not serialized, so the header must cover every request of the batch.
+        let mut betas = self.betas.clone();
+        for request in &requests {
+            for beta in request.params.required_betas() {
+                if !betas.contains(&beta) {
+                    betas.push(beta);
+                }
+            }
+        }
+        let mut request = self.request_url(Method::POST, self.endpoint_url("messages/batches")?)?;
+        if !betas.is_empty() {
+            request = request.header("anthropic-beta", betas.join(","));
+        }
+        let response = self.send(request, &CreateBatch { requests }).await?;
+        self.read_json(response).await
+    }
+
+    pub async fn get_batch(&self, id: &str) -> Result<MessageBatch, AnthropicError> {
+        let request = self.api_request(Method::GET, self.batch_url(id, &[])?)?;
+        let response = self.execute(request, 0).await?;
+        self.read_json(response).await
+    }
+
+    /// Lists all the batches of the workspace, most recently created first.
+    ///
+    /// Follows the pages of [`Client::list_batches_page`] until the last one.
+    pub async fn list_batches(&self) -> Result<Vec<MessageBatch>, AnthropicError> {
+        let mut batches = Vec::new();
+        let mut params = ListBatchesParams::new().with_limit(1000);
+        loop {
+            let page = self.list_batches_page(&params).await?;
+            batches.extend(page.data);
+            match page.last_id {
+                Some(last_id) if page.has_more => params = params.with_after_id(last_id),
+                _ => return Ok(batches),
             }
-            Err(err) => stream::iter(vec![Err(AnthropicError::from(err))]),
+        }
+    }
+
+    /// Lists a single page of the batches of the workspace.
+    pub async fn list_batches_page(
+        &self,
+        params: &ListBatchesParams,
+    ) -> Result<BatchList, AnthropicError> {
+        let request = self.request(Method::GET, "messages/batches")?.query(params);
+        let response = self.execute(request, 0).await?;
+        self.read_json(response).await
+    }
+
+    /// Streams the results of an ended batch, parsed line by line as they are downloaded.
+    ///
+    /// Results are not ordered like the requests of the batch, match them with `custom_id`.
+    pub async fn batch_results(
+        &self,
+        id: &str,
+    ) -> Result<impl Stream<Item = Result<BatchResult, AnthropicError>>, AnthropicError> {
+        let request = self.api_request(Method::GET, self.batch_url(id, &["results"])?)?;
+        let response = self.execute(request, 0).await?;
+        Ok(self.decode_body(response, JsonLinesDecoder::new()))
+    }
+
+    /// Parses the body of `response` with `decoder` as it is downloaded.
+    fn decode_body<D: BodyDecoder>(
+        &self,
+        response: Response,
+        decoder: D,
+    ) -> impl Stream<Item = Result<D::Item, AnthropicError>> {
+        let audit_sink = self.audit_sink.clone();
+        let chunks = response.bytes_stream().inspect(move |chunk| {
+            if let (Some(audit_sink), Ok(bytes)) = (&audit_sink, chunk) {
+                audit_sink.on_response_bytes(bytes);
+            }
+        });
+        stream::unfold(
+            (Box::pin(chunks), decoder, false),
+            |(mut chunks, mut decoder, finished)| async move {
+                if finished {
+                    return None;
+                }
+                match chunks.next().await {
+                    Some(Ok(bytes)) => Some((decoder.decode(&bytes), (chunks, decoder, false))),
+                    Some(Err(err)) => Some((
+                        vec![Err(AnthropicError::from(err))],
+                        (chunks, decoder, false),
+                    )),
+                    None => Some((decoder.finish(), (chunks, decoder, true))),
+                }
+            },
+        )
+        .flat_map(stream::iter)
+    }
+
+    /// Cancels the requests of a batch that are not processed yet.
+    ///
+    /// The batch is `canceling` until the requests being processed complete.
+    pub async fn cancel_batch(&self, id: &str) -> Result<MessageBatch, AnthropicError> {
+        let request = self.api_request(Method::POST, self.batch_url(id, &["cancel"])?)?;
+        let response = self.execute(request, 0).await?;
+        self.read_json(response).await
+    }
+
+    /// Same as [`Client::create_message`] but aborts the in-flight request once `cancel` fires.
+    #[cfg(feature = "cancellation")]
+    pub async fn create_message_with_cancel(
+        &self,
+        payload: MessageRequest,
+        cancel: tokio_util::sync::CancellationToken,
+    ) -> Result<MessageResponse, AnthropicError> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(AnthropicError::Cancelled),
+            result = self.create_message(payload) => result,
+        }
+    }
+
+    /// Sends `request` and streams the response as server-sent events.
+    ///
+    /// `stream` is always enabled on the sent request, whatever its value on `request`.
+    pub async fn stream_message(
+        &self,
+        request: MessageRequest,
+    ) -> Result<MessageStream, AnthropicError> {
+        let request = self.finalize_request(&request.with_stream(true))?;
+        let response = self.send_message(&request).await?;
+
+        let request_id = parse_request_id(response.headers());
+        let raw_frames = self
+            .raw_stream_capture
+            .then(|| Arc::new(Mutex::new(Vec::new())));
+        let decoder = self.backend.stream_decoder(raw_frames.clone());
+        let events = self.decode_body(response, decoder);
+
+        Ok(
+            MessageStream::new(Self::guard_empty_stream(events), raw_frames)
+                .with_max_output_chars(self.max_output_chars)
+                .with_request_id(request_id),
+        )
+    }
+
+    /// Streams several requests concurrently, tagging each event with the index of its request.
+    ///
+    /// Events of a given request keep their order, while events of different requests are
+    /// interleaved as they arrive.
+    pub fn stream_messages(
+        &self,
+        requests: Vec<MessageRequest>,
+    ) -> impl Stream<Item = (usize, Result<StreamEvent, AnthropicError>)> + Unpin + '_ {
+        stream::select_all(requests.into_iter().enumerate().map(|(index, request)| {
+            stream::once(self.stream_message(request))
+                .flat_map(move |result| match result {
+                    Ok(events) => events.map(move |event| (index, event)).left_stream(),
+                    Err(err) => stream::iter(vec![(index, Err(err))]).right_stream(),
+                })
+                .boxed()
         }))
     }
 
-    fn parse_stream_chunk(bytes: &[u8]) -> Vec<Result<StreamEvent, AnthropicError>> {
-        let chunk_str = match std::str::from_utf8(bytes).map_err(AnthropicError::Utf8Error) {
-            Ok(chunk_str) => chunk_str,
-            Err(err) => return vec![Err(err)],
-        };
-        chunk_str
-            .split("\n\n")
-            .filter(|event| !event.trim().is_empty())
-            .map(|event| {
-                event
-                    .lines()
-                    .find(|line| line.starts_with("data: "))
-                    .and_then(|line| line.strip_prefix("data: "))
-                    .ok_or(AnthropicError::InvalidStreamEvent)
-                    .and_then(|content| {
-                        StreamEvent::from_str(content)
-                            .map_err(|_| AnthropicError::InvalidStreamEvent)
-                    })
-            })
-            .collect()
+    /// Yields an `EmptyResponse` error if the stream ends before any `message_start` event.
+    fn guard_empty_stream(
+        events: impl Stream<Item = Result<StreamEvent, AnthropicError>>,
+    ) -> impl Stream<Item = Result<StreamEvent, AnthropicError>> + Unpin {
+        Box::pin(stream::unfold(
+            (Box::pin(events), false, false),
+            |(mut events, started, finished)| async move {
+                if finished {
+                    return None;
+                }
+                match events.next().await {
+                    Some(event) => {
+                        let started =
+                            started || matches!(event, Ok(StreamEvent::MessageStart { .. }));
+                        Some((event, (events, started, false)))
+                    }
+                    None if !started => {
+                        Some((Err(AnthropicError::EmptyResponse), (events, started, true)))
+                    }
+                    None => None,
+                }
+            },
+        ))
+    }
+}
+
+/// Delay of a `retry-after` header in seconds, HTTP dates are ignored.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Parses `base_url` as a directory, so relative joins keep its path, e.g. the prefix of a proxy.
+fn parse_base_url(base_url: &str) -> Result<Url, AnthropicError> {
+    let mut url = Url::parse(base_url).map_err(|err| AnthropicError::UrlParse(err.to_string()))?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+/// Error of a backend other than the Anthropic API, typed after its status code.
+fn backend_error(status: StatusCode, body: &[u8]) -> ApiErrorResponse {
+    // Bedrock reports `{"message"}` and Google APIs `{"error": {"message"}}`.
+    #[derive(Deserialize)]
+    struct BackendError {
+        message: Option<String>,
+        error: Option<Box<BackendError>>,
     }
+    let error_type = match status {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => ApiErrorType::InvalidRequest,
+        StatusCode::UNAUTHORIZED => ApiErrorType::Authentication,
+        StatusCode::FORBIDDEN => ApiErrorType::Permission,
+        StatusCode::NOT_FOUND => ApiErrorType::NotFound,
+        StatusCode::TOO_MANY_REQUESTS => ApiErrorType::RateLimit,
+        StatusCode::SERVICE_UNAVAILABLE => ApiErrorType::Overloaded,
+        _ => ApiErrorType::Unexpected,
+    };
+    let message = serde_json::from_slice::<BackendError>(body)
+        .ok()
+        .and_then(|error| error.message.or_else(|| error.error?.message))
+        .unwrap_or_else(|| status.to_string());
+    ApiErrorResponse {
+        error_type: "error".to_string(),
+        error: ApiErrorDetail {
+            error_type,
+            message,
+        },
+        retry_after: None,
+        rate_limit: None,
+        request_id: None,
+    }
+}
+
+fn parse_request_id(headers: &HeaderMap) -> Option<String> {
+    let request_id = headers.get("request-id")?.to_str().ok()?;
+    Some(request_id.to_string())
+}
+
+/// Beta enabling the token counting endpoint.
+const TOKEN_COUNTING_BETA: &str = "token-counting-2024-11-01";
+
+/// Whether `err` reports that `model` does not exist, e.g. because it was retired.
+fn is_model_not_found(err: &ApiErrorResponse, model: &Model) -> bool {
+    err.error.error_type == ApiErrorType::NotFound && err.error.message.contains(model.as_str())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Transport details of a response, returned by [`Client::create_message_with_meta`].
+///
+/// Errors returned by the API carry the request id in [`ApiErrorResponse::request_id`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseMeta {
+    /// HTTP version negotiated with the server or proxy, e.g. to diagnose HTTP/2 downgrades.
+    pub http_version: Version,
+    /// Value of the `request-id` header, to share with Anthropic support.
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AnthropicVersion {
+    #[default]
     Latest,
     Initial,
 }
 
-impl Default for AnthropicVersion {
-    fn default() -> Self {
-        Self::Latest
+impl AnthropicVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Latest => "2023-06-01",
+            Self::Initial => "2023-01-01",
+        }
+    }
+
+    /// Whether this version is at least as recent as `other`.
+    pub fn supports(&self, other: &AnthropicVersion) -> bool {
+        // Versions are ISO dates, so they compare chronologically as strings.
+        self.as_str() >= other.as_str()
     }
 }
 
 impl fmt::Display for AnthropicVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Latest => write!(f, "2023-06-01"),
-            Self::Initial => write!(f, "2023-01-01"),
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid anthropic-version: {0}")]
+pub struct AnthropicVersionError(String);
+
+impl FromStr for AnthropicVersion {
+    type Err = AnthropicVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "2023-06-01" => Ok(Self::Latest),
+            "2023-01-01" => Ok(Self::Initial),
+            _ => Err(AnthropicVersionError(s.to_string())),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ApiVersion {
+    #[default]
     V1,
 }
 
-impl Default for ApiVersion {
-    fn default() -> Self {
-        Self::V1
-    }
-}
-
 impl fmt::Display for ApiVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -210,3 +911,1738 @@ impl FromStr for ApiVersion {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::batch::{BatchOutcome, BatchStatus};
+    use crate::api::message::{CacheControl, Content, MessageType, Role, RoleResponse, TokenUsage};
+    use crate::circuit_breaker::CircuitBreakerConfig;
+
+    #[test]
+    fn should_send_browser_access_header_only_when_enabled() {
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        assert!(!client
+            .headers
+            .contains_key("anthropic-dangerous-direct-browser-access"));
+
+        let config = Config::new("anthropic-api-key").with_browser_access(true);
+        let client = Client::new(config).unwrap();
+        assert_eq!(
+            client.headers["anthropic-dangerous-direct-browser-access"],
+            "true"
+        );
+    }
+
+    #[test]
+    fn should_keep_base_url_path_prefix() {
+        for (base_url, expected) in [
+            ("https://api.anthropic.com", "https://api.anthropic.com/v1/"),
+            (
+                "https://api.anthropic.com/",
+                "https://api.anthropic.com/v1/",
+            ),
+            (
+                "https://proxy.internal/anthropic",
+                "https://proxy.internal/anthropic/v1/",
+            ),
+            (
+                "https://proxy.internal/anthropic/",
+                "https://proxy.internal/anthropic/v1/",
+            ),
+        ] {
+            let client =
+                Client::new(Config::new("anthropic-api-key").with_base_url(base_url)).unwrap();
+            assert_eq!(client.base_url(), expected);
+            assert_eq!(
+                client.endpoint_url("messages").unwrap().as_str(),
+                format!("{expected}messages")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_send_requests_under_base_url_path_prefix() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/anthropic/v1/messages")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config =
+            Config::new("anthropic-api-key").with_base_url(format!("{}/anthropic", server.url()));
+        let client = Client::new(config).unwrap();
+        client
+            .create_message(MessageRequest::new(Model::Claude3Haiku, 256, Vec::new()))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_send_same_request_for_chat_and_create_message() {
+        let messages = vec![Message {
+            role: Role::User,
+            content: vec![Content::Text {
+                text: "Hello".to_string(),
+                cache_control: None,
+            }],
+        }];
+        let request = MessageRequest {
+            model: Model::Claude3Haiku,
+            max_tokens: 256,
+            messages: messages.clone(),
+            ..Default::default()
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::Json(
+                serde_json::to_value(&request).unwrap(),
+            ))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let manual = client.create_message(request).await.unwrap();
+        let chat = client
+            .chat(Model::Claude3Haiku, 256, messages)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(manual, chat);
+    }
+
+    #[tokio::test]
+    async fn should_return_payload_too_large_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_status(413)
+            .with_body("<html>413 Request Entity Too Large</html>")
+            .create_async()
+            .await;
+
+        let request = MessageRequest::default();
+        let size = serde_json::to_vec(&request).unwrap().len();
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let err = client.create_message(request).await.unwrap_err();
+
+        assert!(matches!(err, AnthropicError::PayloadTooLarge(s) if s == size));
+        assert!(!err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn should_fail_fast_once_circuit_is_open() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .with_status(429)
+            .with_body(
+                r#"{"type":"error","error":{"type":"rate_limit_error","message":"Rate limited"}}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_circuit_breaker(CircuitBreakerConfig::new(
+                2,
+                std::time::Duration::from_secs(60),
+            ));
+        let client = Client::new(config).unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                client.create_message(MessageRequest::default()).await,
+                Err(AnthropicError::Api(_))
+            ));
+        }
+        assert!(matches!(
+            client.create_message(MessageRequest::default()).await,
+            Err(AnthropicError::CircuitOpen)
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_accept_resolved_model_not_enumerated() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "model": "claude-3-7-sonnet-latest" }),
+            ))
+            .with_body(r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-7-sonnet-20250219","content":[{"type":"text","text":"Hi"}],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}"#)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let alias = Model::Custom("claude-3-7-sonnet-latest".to_string());
+        let response = client
+            .create_message(MessageRequest::new(alias, 1024, Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.model,
+            Model::Custom("claude-3-7-sonnet-20250219".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn should_fail_to_serialize_non_finite_temperature() {
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        let request = MessageRequest::default().with_temperature(f64::NAN);
+
+        let result = client
+            .send(client.request(Method::POST, "messages").unwrap(), &request)
+            .await;
+        assert!(matches!(result, Err(AnthropicError::Serialize(_))));
+    }
+
+    #[tokio::test]
+    async fn should_reject_streaming_request_in_create_message() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let result = client
+            .create_message(MessageRequest::default().with_stream(true))
+            .await;
+        assert!(matches!(
+            result,
+            Err(AnthropicError::InvalidParameter {
+                field: "stream",
+                ..
+            })
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_enable_stream_in_stream_message() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "stream": true }),
+            ))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "event: message_start\n",
+                r#"data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+                "\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let mut stream = client
+            .stream_message(MessageRequest::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(StreamEvent::MessageStart { .. }))
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_count_tokens() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages/count_tokens")
+            .match_header("anthropic-beta", TOKEN_COUNTING_BETA)
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "model": "claude-3-haiku-20240307",
+                "messages": [{ "role": "user", "content": [{ "type": "text", "text": "Hello" }] }],
+                "system": "Be brief",
+            })))
+            .with_body(r#"{"input_tokens":14}"#)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let message = Message {
+            role: Role::User,
+            content: vec![Content::Text {
+                text: "Hello".to_string(),
+                cache_control: None,
+            }],
+        };
+        let request = MessageRequest::new(Model::Claude3Haiku, 1024, vec![message])
+            .with_system("Be brief")
+            .with_temperature(0.5);
+        let count = client.count_tokens(request).await.unwrap();
+        assert_eq!(count, TokenCount { input_tokens: 14 });
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_count_tokens_with_betas_required_by_request() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages/count_tokens")
+            .match_header(
+                "anthropic-beta",
+                format!("pdfs-2024-09-25,{TOKEN_COUNTING_BETA}").as_str(),
+            )
+            .with_body(r#"{"input_tokens":3014}"#)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let message = Message {
+            role: Role::User,
+            content: vec![
+                Content::pdf_base64("JVBERi0xLjQK"),
+                Content::text("Summarize"),
+            ],
+        };
+        let request = MessageRequest::new(Model::Claude35SonnetLatest, 1024, vec![message]);
+        let count = client.count_tokens(request).await.unwrap();
+        assert_eq!(count, TokenCount { input_tokens: 3014 });
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn should_estimate_cost_with_configured_pricing() {
+        let model = Model::Custom("claude-gateway".to_string());
+        let config = Config::new("anthropic-api-key")
+            .with_model_pricing(model.clone(), ModelPricing::new(2.0, 10.0));
+        let client = Client::new(config).unwrap();
+
+        let message = Message {
+            role: Role::User,
+            content: vec![Content::Text {
+                text: "a".repeat(4_000_000),
+                cache_control: None,
+            }],
+        };
+        let request = MessageRequest::new(model, 100_000, vec![message]);
+        let estimate = client.estimate_cost(&request).unwrap();
+        assert_eq!(estimate.input_tokens, 1_000_000);
+        assert_eq!(estimate.input_cost, 2.0);
+        assert_eq!(estimate.max_output_cost, 1.0);
+
+        let request =
+            MessageRequest::new(Model::Custom("claude-next".to_string()), 1024, Vec::new());
+        assert!(matches!(
+            client.estimate_cost(&request),
+            Err(AnthropicError::ModelNotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_append_default_query() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "deployment".into(),
+                "eu-west".into(),
+            ))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_default_query(vec![("deployment".to_string(), "eu-west".to_string())]);
+        let client = Client::new(config).unwrap();
+        client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_list_models_across_pages() {
+        let mut server = mockito::Server::new_async().await;
+        let first_page = server
+            .mock("GET", "/v1/models")
+            .match_query(mockito::Matcher::Exact("limit=1000".into()))
+            .with_body(r#"{"data":[{"type":"model","id":"claude-3-5-sonnet-20240620","display_name":"Claude 3.5 Sonnet","created_at":"2024-06-20T00:00:00Z"}],"has_more":true,"first_id":"claude-3-5-sonnet-20240620","last_id":"claude-3-5-sonnet-20240620"}"#)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("GET", "/v1/models")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "after_id".into(),
+                "claude-3-5-sonnet-20240620".into(),
+            ))
+            .with_body(r#"{"data":[{"type":"model","id":"claude-3-haiku-20240307","display_name":"Claude 3 Haiku","created_at":"2024-03-07T00:00:00Z"}],"has_more":false,"first_id":"claude-3-haiku-20240307","last_id":"claude-3-haiku-20240307"}"#)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let models = client.list_models().await.unwrap();
+        let ids: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["claude-3-5-sonnet-20240620", "claude-3-haiku-20240307"]
+        );
+        assert_eq!(Model::from_str(ids[1]).unwrap(), Model::Claude3Haiku);
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+    }
+
+    const BATCH: &str = r#"{"id":"msgbatch_01","type":"message_batch","processing_status":"in_progress","request_counts":{"processing":2,"succeeded":0,"errored":0,"canceled":0,"expired":0},"ended_at":null,"created_at":"2024-10-15T12:00:00Z","expires_at":"2024-10-16T12:00:00Z","cancel_initiated_at":null,"archived_at":null,"results_url":null}"#;
+
+    #[tokio::test]
+    async fn should_create_batch() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages/batches")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "requests": [
+                    { "custom_id": "first", "params": { "model": "claude-3-haiku-20240307" } },
+                    { "custom_id": "second", "params": { "model": "claude-3-haiku-20240307" } },
+                ]
+            })))
+            .with_body(BATCH)
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_default_model(Model::Claude3Haiku);
+        let client = Client::new(config).unwrap();
+        let batch = client
+            .create_batch(vec![
+                BatchRequest::new("first", MessageRequest::default()),
+                BatchRequest::new("second", MessageRequest::default()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(batch.id, "msgbatch_01");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_send_betas_required_by_batch_requests() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages/batches")
+            .match_header(
+                "anthropic-beta",
+                "max-tokens-3-5-sonnet-2024-07-15,prompt-caching-2024-07-31",
+            )
+            .with_body(BATCH)
+            .create_async()
+            .await;
+
+        let cached = Message {
+            role: Role::User,
+            content: vec![
+                Content::text("Long document").with_cache_control(CacheControl::Ephemeral)
+            ],
+        };
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        client
+            .create_batch(vec![
+                BatchRequest::new(
+                    "first",
+                    MessageRequest::new(Model::Claude35Sonnet, 8192, Vec::new()),
+                ),
+                BatchRequest::new(
+                    "second",
+                    MessageRequest::new(Model::Claude3Haiku, 1024, vec![cached]),
+                ),
+                BatchRequest::new(
+                    "third",
+                    MessageRequest::new(Model::Claude35Sonnet, 8192, Vec::new()),
+                ),
+            ])
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_reject_streaming_batch_request() {
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        let result = client
+            .create_batch(vec![BatchRequest::new(
+                "first",
+                MessageRequest::default().with_stream(true),
+            )])
+            .await;
+        assert!(matches!(
+            result,
+            Err(AnthropicError::InvalidParameter {
+                field: "stream",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_get_and_cancel_batch() {
+        let mut server = mockito::Server::new_async().await;
+        let get = server
+            .mock("GET", "/v1/messages/batches/msgbatch_01")
+            .with_body(BATCH)
+            .create_async()
+            .await;
+        let cancel = server
+            .mock("POST", "/v1/messages/batches/msgbatch_01/cancel")
+            .with_body(BATCH.replace("in_progress", "canceling"))
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let batch = client.get_batch("msgbatch_01").await.unwrap();
+        assert_eq!(batch.processing_status, BatchStatus::InProgress);
+        let batch = client.cancel_batch("msgbatch_01").await.unwrap();
+        assert_eq!(batch.processing_status, BatchStatus::Canceling);
+        get.assert_async().await;
+        cancel.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_escape_batch_id_in_path() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                "/v1/messages/batches/msgbatch%2F..%3Fid=1%23/results",
+            )
+            .with_body("")
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let results: Vec<_> = client
+            .batch_results("msgbatch/..?id=1#")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(results.is_empty());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_list_batches() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/messages/batches")
+            .match_query(mockito::Matcher::Exact("limit=1000".into()))
+            .with_body(format!(
+                r#"{{"data":[{BATCH}],"has_more":false,"first_id":"msgbatch_01","last_id":"msgbatch_01"}}"#
+            ))
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let batches = client.list_batches().await.unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].id, "msgbatch_01");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_stream_batch_results() {
+        let succeeded = BatchResult {
+            custom_id: "first".to_string(),
+            result: BatchOutcome::Succeeded {
+                message: MessageResponse::mock_text("Hi"),
+            },
+        };
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/v1/messages/batches/msgbatch_01/results")
+            .with_body(format!(
+                "{}\n{}\n",
+                serde_json::json!({
+                    "custom_id": "first",
+                    "result": { "type": "succeeded", "message": MessageResponse::mock_text("Hi") },
+                }),
+                r#"{"custom_id":"second","result":{"type":"expired"}}"#,
+            ))
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let results: Vec<BatchResult> = client
+            .batch_results("msgbatch_01")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            results,
+            vec![
+                succeeded,
+                BatchResult {
+                    custom_id: "second".to_string(),
+                    result: BatchOutcome::Expired,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_send_beta_header_only_when_needed() {
+        let mut server = mockito::Server::new_async().await;
+        let with_beta = server
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", "max-tokens-3-5-sonnet-2024-07-15")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+        let without_beta = server
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", mockito::Matcher::Missing)
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        for max_tokens in [8192, 1024] {
+            client
+                .create_message(MessageRequest::new(
+                    Model::Claude35Sonnet,
+                    max_tokens,
+                    Vec::new(),
+                ))
+                .await
+                .unwrap();
+        }
+        with_beta.assert_async().await;
+        without_beta.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_send_prompt_caching_beta_only_with_cache_control() {
+        let mut server = mockito::Server::new_async().await;
+        let with_beta = server
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", "prompt-caching-2024-07-31")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+        let without_beta = server
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", mockito::Matcher::Missing)
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        for cache_control in [Some(CacheControl::Ephemeral), None] {
+            let mut content = Content::text("Long document");
+            if let Some(cache_control) = cache_control {
+                content = content.with_cache_control(cache_control);
+            }
+            let message = Message {
+                role: Role::User,
+                content: vec![content],
+            };
+            client
+                .create_message(MessageRequest::new(
+                    Model::Claude3Haiku,
+                    1024,
+                    vec![message],
+                ))
+                .await
+                .unwrap();
+        }
+        with_beta.assert_async().await;
+        without_beta.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_join_config_and_request_betas_in_one_header() {
+        let mut server = mockito::Server::new_async().await;
+        let messages = server
+            .mock("POST", "/v1/messages")
+            .match_header(
+                "anthropic-beta",
+                "token-efficient-tools-2025-02-19,pdfs-2024-09-25,max-tokens-3-5-sonnet-2024-07-15",
+            )
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+        let batch = server
+            .mock("GET", "/v1/messages/batches/batch_id")
+            .match_header(
+                "anthropic-beta",
+                "token-efficient-tools-2025-02-19,pdfs-2024-09-25",
+            )
+            .with_status(404)
+            .with_body(
+                r#"{"type":"error","error":{"type":"not_found_error","message":"Not found"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_beta("token-efficient-tools-2025-02-19")
+            .with_beta("pdfs-2024-09-25");
+        let client = Client::new(config).unwrap();
+        client
+            .create_message(
+                MessageRequest::new(Model::Claude35Sonnet, 8192, Vec::new())
+                    .with_beta("pdfs-2024-09-25"),
+            )
+            .await
+            .unwrap();
+        client.get_batch("batch_id").await.unwrap_err();
+
+        messages.assert_async().await;
+        batch.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_return_response_http_version() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let (response, meta) = client
+            .create_message_with_meta(MessageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hi");
+        assert_eq!(meta.http_version, Version::HTTP_11);
+    }
+
+    #[tokio::test]
+    async fn should_expose_request_id_of_responses_and_streams() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "stream": false }),
+            ))
+            .with_header("request-id", "req_01")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "stream": true }),
+            ))
+            .with_header("content-type", "text/event-stream")
+            .with_header("request-id", "req_02")
+            .with_body(concat!(
+                "event: message_stop\n",
+                r#"data: {"type":"message_stop"}"#,
+                "\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let (_, meta) = client
+            .create_message_with_meta(MessageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(meta.request_id.as_deref(), Some("req_01"));
+
+        let stream = client
+            .stream_message(MessageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(stream.request_id(), Some("req_02"));
+    }
+
+    #[tokio::test]
+    async fn should_retry_with_fallback_model() {
+        let mut server = mockito::Server::new_async().await;
+        let not_found = server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "model": Model::Claude3Opus }),
+            ))
+            .with_status(404)
+            .with_body(r#"{"type":"error","error":{"type":"not_found_error","message":"model: claude-3-opus-20240229"}}"#)
+            .create_async()
+            .await;
+        let fallback = server
+            .mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "model": Model::Claude35Sonnet }),
+            ))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_model_fallbacks(HashMap::from([(Model::Claude3Opus, Model::Claude35Sonnet)]));
+        let client = Client::new(config).unwrap();
+
+        let response = client
+            .create_message(MessageRequest::new(Model::Claude3Opus, 1024, Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hi");
+        not_found.assert_async().await;
+        fallback.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_retry_rate_limited_request() {
+        let mut server = mockito::Server::new_async().await;
+        let rate_limited = server
+            .mock("POST", "/v1/messages")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .with_body(
+                r#"{"type":"error","error":{"type":"rate_limit_error","message":"Rate limited"}}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/v1/messages")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_retry(RetryConfig::new(2));
+        let client = Client::new(config).unwrap();
+
+        let response = client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hi");
+        rate_limited.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_stop_retrying_after_max_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let overloaded = server
+            .mock("POST", "/v1/messages")
+            .with_status(529)
+            .with_body(
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            )
+            .expect(3)
+            .create_async()
+            .await;
+
+        let retry = RetryConfig::new(2)
+            .with_base_delay(std::time::Duration::from_millis(1))
+            .with_jitter(false);
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_retry(retry);
+        let client = Client::new(config).unwrap();
+
+        let err = client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AnthropicError::Api(_)));
+        overloaded.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_invalid_request() {
+        let mut server = mockito::Server::new_async().await;
+        let invalid = server
+            .mock("POST", "/v1/messages")
+            .with_status(400)
+            .with_body(
+                r#"{"type":"error","error":{"type":"invalid_request_error","message":"Invalid"}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_retry(RetryConfig::new(2));
+        let client = Client::new(config).unwrap();
+
+        assert!(client
+            .create_message(MessageRequest::default())
+            .await
+            .is_err());
+        invalid.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_expose_rate_limit_headers_on_api_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_status(429)
+            .with_header("retry-after", "12")
+            .with_header("request-id", "req_018EeWyXxfu5pfWkrYcMdjWG")
+            .with_header("anthropic-ratelimit-requests-remaining", "0")
+            .with_header("anthropic-ratelimit-tokens-remaining", "25000")
+            .with_body(
+                r#"{"type":"error","error":{"type":"rate_limit_error","message":"Rate limited"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let err = client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap_err();
+
+        let AnthropicError::Api(api_error) = err else {
+            panic!("Expected an API error, got {err:?}");
+        };
+        assert_eq!(api_error.retry_after, Some(Duration::from_secs(12)));
+        assert_eq!(
+            api_error.request_id.as_deref(),
+            Some("req_018EeWyXxfu5pfWkrYcMdjWG")
+        );
+        let rate_limit = api_error.rate_limit.unwrap();
+        assert_eq!(rate_limit.requests_remaining, Some(0));
+        assert_eq!(rate_limit.tokens_remaining, Some(25000));
+    }
+
+    #[test]
+    fn should_parse_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[derive(Default)]
+    struct MemoryAuditSink {
+        requests: Mutex<Vec<Vec<u8>>>,
+        responses: Mutex<Vec<u8>>,
+    }
+
+    impl AuditSink for MemoryAuditSink {
+        fn on_request_bytes(&self, bytes: &[u8]) {
+            self.requests.lock().unwrap().push(bytes.to_vec());
+        }
+
+        fn on_response_bytes(&self, bytes: &[u8]) {
+            self.responses.lock().unwrap().extend_from_slice(bytes);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_report_bodies_to_audit_sink() {
+        let body = serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_body(&body)
+            .create_async()
+            .await;
+
+        let audit_sink = Arc::new(MemoryAuditSink::default());
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_audit_sink(audit_sink.clone());
+        let client = Client::new(config).unwrap();
+
+        let request = MessageRequest::default().with_system("Be brief");
+        client.create_message(request.clone()).await.unwrap();
+
+        let requests = audit_sink.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0], serde_json::to_vec(&request).unwrap());
+        assert_eq!(*audit_sink.responses.lock().unwrap(), body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn should_report_stream_chunks_to_audit_sink() {
+        let body = concat!(
+            "event: message_start\n",
+            r#"data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+            "\n\n",
+            "event: message_stop\n",
+            r#"data: {"type":"message_stop"}"#,
+            "\n\n",
+        );
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let audit_sink = Arc::new(MemoryAuditSink::default());
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_audit_sink(audit_sink.clone());
+        let client = Client::new(config).unwrap();
+
+        let stream = client
+            .stream_message(MessageRequest::default())
+            .await
+            .unwrap();
+        let _: Vec<_> = stream.collect().await;
+
+        assert_eq!(audit_sink.requests.lock().unwrap().len(), 1);
+        assert_eq!(*audit_sink.responses.lock().unwrap(), body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn should_close_connection_when_stream_is_dropped() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 4096];
+            let _ = socket.read(&mut buffer).await;
+
+            let frame = concat!(
+                "data: ",
+                r#"{"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+                "\n\n"
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n{:x}\r\n{frame}\r\n",
+                frame.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            // The response is never completed, so the socket only reads EOF once the client closes it.
+            while !matches!(socket.read(&mut buffer).await, Ok(0) | Err(_)) {}
+            let _ = closed_tx.send(());
+        });
+
+        let config = Config::new("anthropic-api-key").with_base_url(format!("http://{address}"));
+        let client = Client::new(config).unwrap();
+        let mut stream = client
+            .stream_message(MessageRequest::default().with_stream(true))
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(StreamEvent::MessageStart { .. }))
+        ));
+
+        drop(stream);
+        tokio::time::timeout(std::time::Duration::from_secs(5), closed_rx)
+            .await
+            .expect("connection should be closed after dropping the stream")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_stream_multiple_messages_concurrently() {
+        fn sse_body(text: &str) -> String {
+            [
+                r#"{"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#.to_string(),
+                format!(r#"{{"type":"content_block_delta","index":0,"delta":{{"type":"text_delta","text":"{text}"}}}}"#),
+                r#"{"type":"message_stop"}"#.to_string(),
+            ]
+            .iter()
+            .map(|data| format!("data: {data}\n\n"))
+            .collect()
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        for (model, text) in [(Model::Claude3Haiku, "haiku"), (Model::Claude3Opus, "opus")] {
+            server
+                .mock("POST", "/v1/messages")
+                .match_body(mockito::Matcher::PartialJson(
+                    serde_json::json!({ "model": model }),
+                ))
+                .with_header("content-type", "text/event-stream")
+                .with_body(sse_body(text))
+                .create_async()
+                .await;
+        }
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let requests = vec![
+            MessageRequest::new(Model::Claude3Haiku, 1024, Vec::new()).with_stream(true),
+            MessageRequest::new(Model::Claude3Opus, 1024, Vec::new()).with_stream(true),
+        ];
+        let events: Vec<(usize, Result<StreamEvent, AnthropicError>)> =
+            client.stream_messages(requests).collect().await;
+
+        assert_eq!(events.len(), 6);
+        for (index, text) in [(0, "haiku"), (1, "opus")] {
+            let events: Vec<&StreamEvent> = events
+                .iter()
+                .filter(|(i, _)| *i == index)
+                .map(|(_, event)| event.as_ref().unwrap())
+                .collect();
+            assert!(matches!(events[0], StreamEvent::MessageStart { .. }));
+            assert!(
+                matches!(events[1], StreamEvent::ContentBlockDelta(content) if content.delta.text == text)
+            );
+            assert_eq!(events[2], &StreamEvent::MessageStop);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_end_stream_with_mid_stream_error_event() {
+        let body: String = [
+            ("message_start", r#"{"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#),
+            ("content_block_start", r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#),
+            ("content_block_delta", r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#),
+            ("error", r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+            ("content_block_delta", r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" world"}}"#),
+        ]
+        .iter()
+        .map(|(event, data)| format!("event: {event}\ndata: {data}\n\n"))
+        .collect();
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_header("request-id", "req_01")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let mut events = client
+            .stream_message(MessageRequest::new(Model::Claude3Haiku, 1024, Vec::new()))
+            .await
+            .unwrap();
+
+        for _ in 0..3 {
+            events.next().await.unwrap().unwrap();
+        }
+        let err = events.next().await.unwrap().unwrap_err();
+        assert_eq!(err.api_error_type(), Some(ApiErrorType::Overloaded));
+        assert_eq!(err.request_id(), Some("req_01"));
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_return_api_error_for_successful_error_body() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_body(
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let err = client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            AnthropicError::Api(ApiErrorResponse { error, .. })
+                if error.error_type == crate::error::ApiErrorType::Overloaded
+                    && error.message == "Overloaded"
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_oversized_request_before_sending() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_max_request_bytes(256);
+        let client = Client::new(config).unwrap();
+        let request = MessageRequest::default().with_system("x".repeat(512));
+        let size = serde_json::to_vec(&request).unwrap().len();
+
+        assert!(matches!(
+            client.create_message(request).await,
+            Err(AnthropicError::PayloadTooLarge(s)) if s == size
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_capture_raw_stream_frames() {
+        let frames = [
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-sonnet-20240620","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+            r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+            r#"event: message_stop
+data: {"type":"message_stop"}"#,
+        ];
+        let body: String = frames.iter().map(|frame| format!("{frame}\n\n")).collect();
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_body(&body)
+            .create_async()
+            .await;
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(server.url())
+            .with_raw_stream_capture(true);
+        let client = Client::new(config).unwrap();
+        let mut stream = client
+            .stream_message(MessageRequest::default().with_stream(true))
+            .await
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = stream.next().await {
+            events.push(event.unwrap());
+        }
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(stream.raw_frames(), frames);
+    }
+
+    #[test]
+    fn should_finalize_request_with_client_defaults() {
+        let config = Config::new("anthropic-api-key").with_default_model(Model::Claude3Haiku);
+        let client = Client::new(config).unwrap();
+
+        let request = MessageRequest::default().with_system("Be concise");
+        let finalized = client.finalize_request(&request).unwrap();
+        assert_eq!(finalized.model, Model::Claude3Haiku);
+        assert_eq!(finalized.system, request.system);
+        assert_eq!(finalized.max_tokens, request.max_tokens);
+
+        let request = MessageRequest::default().with_max_tokens(0);
+        assert!(matches!(
+            client.finalize_request(&request),
+            Err(AnthropicError::InvalidParameter { .. })
+        ));
+    }
+
+    #[test]
+    fn should_prefer_explicit_model_over_default_model() {
+        let config = Config::new("anthropic-api-key").with_default_model(Model::Claude3Haiku);
+        let client = Client::new(config).unwrap();
+
+        assert_eq!(client.resolve_model(Model::Claude3Opus), Model::Claude3Opus);
+        assert_eq!(client.resolve_model(Model::default()), Model::Claude3Haiku);
+    }
+
+    #[test]
+    fn should_keep_request_model_without_default_model() {
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+
+        assert_eq!(client.default_model(), None);
+        assert_eq!(client.resolve_model(Model::default()), Model::default());
+        assert_eq!(client.resolve_model(Model::Claude3Opus), Model::Claude3Opus);
+    }
+
+    #[test]
+    fn should_reject_model_requiring_newer_anthropic_version() {
+        let config =
+            Config::new("anthropic-api-key").with_anthropic_version(AnthropicVersion::Initial);
+        let client = Client::new(config).unwrap();
+        assert!(matches!(
+            client.check_model_version(&Model::Claude35Sonnet),
+            Err(AnthropicError::UnsupportedAnthropicVersion(
+                Model::Claude35Sonnet,
+                AnthropicVersion::Latest
+            ))
+        ));
+
+        assert!(client.check_model_version(&Model::Claude3Opus).is_ok());
+        assert!(client
+            .check_model_version(&Model::Custom("claude-unknown".to_string()))
+            .is_ok());
+
+        let client = Client::new(Config::new("anthropic-api-key")).unwrap();
+        assert!(client.check_model_version(&Model::Claude35Sonnet).is_ok());
+    }
+
+    #[test]
+    fn should_skip_anthropic_version_check_on_other_backends() {
+        let config = Config::new("")
+            .with_anthropic_version(AnthropicVersion::Initial)
+            .with_backend(Backend::vertex("my-project", "us-east5", "access-token"));
+        let client = Client::new(config).unwrap();
+
+        assert!(client.check_model_version(&Model::Claude35Sonnet).is_ok());
+    }
+
+    #[test]
+    fn should_compare_anthropic_versions() {
+        assert!(AnthropicVersion::Latest.supports(&AnthropicVersion::Initial));
+        assert!(AnthropicVersion::Latest.supports(&AnthropicVersion::Latest));
+        assert!(!AnthropicVersion::Initial.supports(&AnthropicVersion::Latest));
+    }
+
+    #[cfg(feature = "secrecy")]
+    #[tokio::test]
+    async fn should_authenticate_with_secret_api_key() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_header("x-api-key", "secret-api-key")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config = Config::from_secret(secrecy::SecretString::from("secret-api-key"))
+            .with_base_url(server.url());
+        assert!(!format!("{config:?}").contains("secret-api-key"));
+
+        let client = Client::new(config).unwrap();
+        client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn should_report_timeout_distinctly() {
+        // Accepts connections but never answers, so only the timeout ends the request.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let config = Config::new("anthropic-api-key")
+            .with_base_url(format!("http://{address}"))
+            .with_timeout(std::time::Duration::from_millis(50));
+        let client = Client::new(config).unwrap();
+
+        let err = client
+            .create_message(MessageRequest::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AnthropicError::Timeout(_)));
+        assert!(!err.is_retryable());
+    }
+
+    #[cfg(feature = "cancellation")]
+    #[tokio::test]
+    async fn should_cancel_in_flight_request() {
+        // Accepts connections but never answers, keeping the request in flight.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let config = Config::new("anthropic-api-key").with_base_url(format!("http://{address}"));
+        let client = Client::new(config).unwrap();
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        let result = client
+            .create_message_with_cancel(MessageRequest::default(), cancel)
+            .await;
+        assert!(matches!(result, Err(AnthropicError::Cancelled)));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn should_record_user_id_on_request_span() {
+        use crate::api::message::MessageMetadata;
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::{layer::SubscriberExt, Layer};
+
+        #[derive(Clone, Default)]
+        struct SpanFields(Arc<Mutex<Vec<(String, String)>>>);
+
+        impl tracing::field::Visit for SpanFields {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((field.name().to_string(), format!("{value:?}")));
+            }
+        }
+
+        impl<S: tracing::Subscriber> Layer<S> for SpanFields {
+            fn on_new_span(
+                &self,
+                attrs: &tracing::span::Attributes<'_>,
+                _id: &tracing::span::Id,
+                _ctx: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                attrs.record(&mut self.clone());
+            }
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let fields = SpanFields::default();
+        let subscriber = tracing_subscriber::registry().with(fields.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client =
+            Client::new(Config::new("anthropic-api-key").with_base_url(server.url())).unwrap();
+        let request = MessageRequest::default().with_metadata(MessageMetadata {
+            user_id: Some("user-42".to_string()),
+        });
+        client.create_message(request).await.unwrap();
+
+        let fields = fields.0.lock().unwrap();
+        assert!(fields.contains(&("user_id".to_string(), "\"user-42\"".to_string())));
+        assert!(fields.contains(&("model".to_string(), Model::default().to_string())));
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_stream_closed_before_message_start() {
+        let mut events = Client::guard_empty_stream(stream::empty());
+        assert!(matches!(
+            events.next().await,
+            Some(Err(AnthropicError::EmptyResponse))
+        ));
+        assert!(events.next().await.is_none());
+
+        let mut events = Client::guard_empty_stream(stream::iter(vec![Ok(StreamEvent::Ping)]));
+        assert!(matches!(events.next().await, Some(Ok(StreamEvent::Ping))));
+        assert!(matches!(
+            events.next().await,
+            Some(Err(AnthropicError::EmptyResponse))
+        ));
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_pass_through_started_stream() {
+        let message = MessageResponse {
+            id: "msg_id".to_string(),
+            message_type: MessageType::Message,
+            role: RoleResponse::Assistant,
+            content: Vec::new(),
+            model: Model::Claude35Sonnet,
+            stop_reason: None,
+            stop_sequence: None,
+            usage: TokenUsage {
+                input_tokens: 1,
+                output_tokens: 1,
+                cache_creation_input_tokens: None,
+                cache_read_input_tokens: None,
+                server_tool_use: None,
+            },
+        };
+        let mut events = Client::guard_empty_stream(stream::iter(vec![
+            Ok(StreamEvent::MessageStart { message }),
+            Ok(StreamEvent::MessageStop),
+        ]));
+        assert!(matches!(
+            events.next().await,
+            Some(Ok(StreamEvent::MessageStart { .. }))
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(Ok(StreamEvent::MessageStop))
+        ));
+        assert!(events.next().await.is_none());
+    }
+
+    #[cfg(feature = "bedrock")]
+    fn bedrock_client(base_url: String) -> Client {
+        use crate::backend::bedrock::AwsCredentials;
+
+        let backend = Backend::bedrock(
+            "us-east-1",
+            AwsCredentials::new("AKIDEXAMPLE", "secret-access-key"),
+        );
+        let config = Config::new("")
+            .with_backend(backend)
+            .with_base_url(base_url);
+        Client::new(config).unwrap()
+    }
+
+    #[cfg(feature = "bedrock")]
+    #[tokio::test]
+    async fn should_send_signed_bedrock_request() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke",
+            )
+            .match_header(
+                "authorization",
+                mockito::Matcher::Regex(
+                    r"^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/\d{8}/us-east-1/bedrock/aws4_request, SignedHeaders=host;x-amz-date, Signature=[0-9a-f]{64}$".to_string(),
+                ),
+            )
+            .match_header("x-api-key", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "anthropic_version": "bedrock-2023-05-31",
+                "max_tokens": 256,
+            })))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let client = bedrock_client(server.url());
+        let response = client
+            .create_message(MessageRequest::new(
+                Model::Claude3Haiku,
+                256,
+                vec![Message::user("Hello")],
+            ))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.first_text(), Some("Hi"));
+    }
+
+    #[cfg(feature = "bedrock")]
+    #[tokio::test]
+    async fn should_stream_bedrock_event_stream() {
+        use crate::backend::bedrock::tests::encode_event;
+
+        let message = serde_json::to_string(&MessageResponse::mock_text("")).unwrap();
+        let mut body = encode_event(&format!(
+            r#"{{"type":"message_start","message":{message}}}"#
+        ));
+        body.extend(encode_event(r#"{"type":"message_stop"}"#));
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke-with-response-stream",
+            )
+            .match_header("accept", "application/vnd.amazon.eventstream")
+            .with_header("content-type", "application/vnd.amazon.eventstream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let client = bedrock_client(server.url());
+        let events: Vec<_> = client
+            .stream_message(MessageRequest::new(
+                Model::Claude3Haiku,
+                256,
+                vec![Message::user("Hello")],
+            ))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.as_slice(),
+            [
+                Ok(StreamEvent::MessageStart { .. }),
+                Ok(StreamEvent::MessageStop)
+            ]
+        ));
+    }
+
+    #[cfg(feature = "bedrock")]
+    #[tokio::test]
+    async fn should_reject_endpoints_unsupported_by_bedrock() {
+        let client = bedrock_client("https://bedrock-runtime.us-east-1.amazonaws.com".to_string());
+        let err = client.get_batch("batch_id").await.unwrap_err();
+        assert!(
+            matches!(err, AnthropicError::UnsupportedByBackend(path) if path == "messages/batches")
+        );
+    }
+
+    #[cfg(feature = "bedrock")]
+    #[tokio::test]
+    async fn should_map_bedrock_errors_by_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke",
+            )
+            .with_status(429)
+            .with_body(r#"{"message":"Too many requests, please wait before trying again."}"#)
+            .create_async()
+            .await;
+
+        let client = bedrock_client(server.url());
+        let err = client
+            .create_message(MessageRequest::new(
+                Model::Claude3Haiku,
+                256,
+                vec![Message::user("Hello")],
+            ))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            AnthropicError::Api(ApiErrorResponse { error, .. }) if error.error_type == ApiErrorType::RateLimit
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_send_vertex_request_with_bearer_token() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/v1/projects/my-project/locations/us-east5/publishers/anthropic/models/claude-3-haiku@20240307:rawPredict",
+            )
+            .match_header("authorization", "Bearer access-token")
+            .match_header("x-api-key", mockito::Matcher::Missing)
+            .match_header("anthropic-version", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "anthropic_version": "vertex-2023-10-16",
+                "max_tokens": 256,
+            })))
+            .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+            .create_async()
+            .await;
+
+        let config = Config::new("")
+            .with_backend(Backend::vertex("my-project", "us-east5", "access-token"))
+            .with_base_url(server.url());
+        let client = Client::new(config).unwrap();
+        let response = client
+            .create_message(MessageRequest::new(
+                Model::Claude3Haiku,
+                256,
+                vec![Message::user("Hello")],
+            ))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.first_text(), Some("Hi"));
+    }
+
+    #[tokio::test]
+    async fn should_request_vertex_token_for_every_request() {
+        use crate::backend::vertex::VertexConfig;
+
+        let mut server = mockito::Server::new_async().await;
+        let path = "/v1/projects/my-project/locations/us-east5/publishers/anthropic/models/claude-3-haiku@20240307:rawPredict";
+        let mut mocks = Vec::new();
+        for token in ["Bearer token-1", "Bearer token-2"] {
+            mocks.push(
+                server
+                    .mock("POST", path)
+                    .match_header("authorization", token)
+                    .with_body(serde_json::to_string(&MessageResponse::mock_text("Hi")).unwrap())
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let refreshes = std::sync::atomic::AtomicUsize::new(0);
+        let provider = move || {
+            let refresh = refreshes.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(format!("token-{refresh}"))
+        };
+        let backend = Backend::Vertex(VertexConfig::with_token_provider(
+            "my-project",
+            "us-east5",
+            provider,
+        ));
+        let config = Config::new("")
+            .with_backend(backend)
+            .with_base_url(server.url());
+        let client = Client::new(config).unwrap();
+        for _ in 0..2 {
+            client
+                .create_message(MessageRequest::new(Model::Claude3Haiku, 256, Vec::new()))
+                .await
+                .unwrap();
+        }
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn should_fail_when_vertex_token_is_unavailable() {
+        use crate::backend::vertex::VertexConfig;
+
+        let provider = || {
+            Err(AnthropicError::Credentials(
+                "no credentials found".to_string(),
+            ))
+        };
+        let backend = Backend::Vertex(VertexConfig::with_token_provider(
+            "my-project",
+            "us-east5",
+            provider,
+        ));
+        let client = Client::new(Config::new("").with_backend(backend)).unwrap();
+        let result = client
+            .create_message(MessageRequest::new(Model::Claude3Haiku, 256, Vec::new()))
+            .await;
+        assert!(matches!(result, Err(AnthropicError::Credentials(_))));
+    }
+}
@@ -1,29 +1,170 @@
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, path::Path, sync::Arc, time::Duration};
+
 use crate::{
+    audit::AuditSink,
+    backend::Backend,
+    circuit_breaker::CircuitBreakerConfig,
     client::{AnthropicVersion, ApiVersion},
     error::AnthropicError,
+    models::model::{Model, ModelPricing},
+    retry::RetryConfig,
 };
 
-const DEFAULT_API_BASE_URL: &str = "https://api.anthropic.com";
+pub(crate) const DEFAULT_API_BASE_URL: &str = "https://api.anthropic.com";
 const API_KEY_ENV_VAR: &str = "ANTHROPIC_API_KEY";
+const BASE_URL_ENV_VAR: &str = "ANTHROPIC_BASE_URL";
+const ANTHROPIC_VERSION_ENV_VAR: &str = "ANTHROPIC_VERSION";
+const API_VERSION_ENV_VAR: &str = "ANTHROPIC_API_VERSION";
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 8;
+const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const DEFAULT_MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// API key redacted from `Debug` output, stored as a [`secrecy::SecretString`] zeroized on drop
+/// when the `secrecy` feature is enabled.
+#[derive(Clone)]
+pub(crate) struct SecretKey(
+    #[cfg(feature = "secrecy")] secrecy::SecretString,
+    #[cfg(not(feature = "secrecy"))] String,
+);
+
+impl SecretKey {
+    pub(crate) fn expose(&self) -> &str {
+        #[cfg(feature = "secrecy")]
+        {
+            use secrecy::ExposeSecret;
+            self.0.expose_secret()
+        }
+        #[cfg(not(feature = "secrecy"))]
+        {
+            self.0.as_str()
+        }
+    }
+}
 
-#[derive(Debug, Clone)]
+impl From<String> for SecretKey {
+    fn from(api_key: String) -> Self {
+        #[cfg(feature = "secrecy")]
+        let api_key = secrecy::SecretString::from(api_key);
+        Self(api_key)
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl From<secrecy::SecretString> for SecretKey {
+    fn from(api_key: secrecy::SecretString) -> Self {
+        Self(api_key)
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub anthropic_version: AnthropicVersion,
-    pub api_key: String,
+    pub(crate) api_key: SecretKey,
     pub api_version: ApiVersion,
+    /// Receives the raw bodies of requests and responses, disabled by default.
+    pub audit_sink: Option<Arc<dyn AuditSink>>,
+    /// Platform serving the requests, the Anthropic API by default.
+    pub backend: Backend,
     pub base_url: String,
+    /// Beta features enabled for every request, sent in the `anthropic-beta` header.
+    ///
+    /// Requests add their own [`MessageRequest::betas`] after these.
+    ///
+    /// [`MessageRequest::betas`]: crate::api::message::MessageRequest::betas
+    pub betas: Vec<String>,
+    /// Circuit breaker shared by all requests of the client, disabled by default.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Whether to send the `anthropic-dangerous-direct-browser-access` header.
+    pub browser_access: bool,
+    /// Model used for requests that leave `model` at its default value.
+    pub default_model: Option<Model>,
+    /// Query parameters appended to every request, e.g. a gateway deployment id.
+    pub default_query: Vec<(String, String)>,
+    /// Models retried once in place of a requested model the API reports as not found.
+    pub model_fallbacks: HashMap<Model, Model>,
+    /// Prices used by [`Client::estimate_cost`] instead of the list prices of [`Model::pricing`].
+    ///
+    /// [`Client::estimate_cost`]: crate::client::Client::estimate_cost
+    pub model_pricing: HashMap<Model, ModelPricing>,
+    /// Maximum number of idle connections kept alive per host.
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection is kept alive in the pool.
+    pub pool_idle_timeout: Duration,
+    /// Maximum size in bytes of images downloaded by [`Content::image_fetch`].
+    ///
+    /// [`Content::image_fetch`]: crate::api::message::Content::image_fetch
+    pub max_image_size: usize,
+    /// Requests whose serialized body exceeds this size are rejected before being sent.
+    pub max_request_bytes: usize,
+    /// Streams are terminated once their text output exceeds this number of characters.
+    pub max_output_chars: Option<usize>,
+    /// Whether streams record the raw text of the frames they receive, for debugging.
+    pub raw_stream_capture: bool,
+    /// Retries of rate limited and failed requests, disabled by default.
+    pub retry: Option<RetryConfig>,
+    /// Total time allowed for a request, from connecting until the response body is read.
+    pub timeout: Option<Duration>,
+    /// Time allowed to establish a connection, independently of `timeout`.
+    pub connect_timeout: Option<Duration>,
 }
 
 impl Config {
     pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_secret_key(SecretKey::from(api_key.into()))
+    }
+
+    /// Creates a configuration from an API key that is never copied out of its
+    /// [`secrecy::SecretString`].
+    #[cfg(feature = "secrecy")]
+    pub fn from_secret(api_key: secrecy::SecretString) -> Self {
+        Self::with_secret_key(SecretKey::from(api_key))
+    }
+
+    fn with_secret_key(api_key: SecretKey) -> Self {
         Self {
             anthropic_version: AnthropicVersion::default(),
-            api_key: api_key.into(),
+            api_key,
             api_version: ApiVersion::default(),
+            audit_sink: None,
+            backend: Backend::default(),
             base_url: DEFAULT_API_BASE_URL.to_string(),
+            betas: Vec::new(),
+            browser_access: false,
+            circuit_breaker: None,
+            default_model: None,
+            default_query: Vec::new(),
+            model_fallbacks: HashMap::new(),
+            model_pricing: HashMap::new(),
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+            pool_idle_timeout: DEFAULT_POOL_IDLE_TIMEOUT,
+            max_image_size: DEFAULT_MAX_IMAGE_SIZE,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            max_output_chars: None,
+            raw_stream_capture: false,
+            retry: None,
+            timeout: None,
+            connect_timeout: None,
         }
     }
 
+    pub fn api_key(&self) -> &str {
+        self.api_key.expose()
+    }
+
+    /// Starts a [`ConfigBuilder`], an alternative to [`Config::new`] for configurations assembled
+    /// from optional settings.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
     pub fn with_anthropic_version(mut self, version: AnthropicVersion) -> Self {
         self.anthropic_version = version;
         self
@@ -34,16 +175,320 @@ impl Config {
         self
     }
 
+    /// Reports the bytes of every request and response body to `audit_sink`.
+    pub fn with_audit_sink(mut self, audit_sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(audit_sink);
+        self
+    }
+
+    /// Sends requests to `backend` and points `base_url` to its default endpoint.
+    ///
+    /// The API key is ignored by backends with their own authentication.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.base_url = backend.base_url();
+        self.backend = backend;
+        self
+    }
+
     pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
         self.base_url = base_url.into();
         self
     }
 
+    /// Enables a beta feature for every request, e.g. one not yet supported by this crate.
+    pub fn with_beta(mut self, beta: impl Into<String>) -> Self {
+        self.betas.push(beta.into());
+        self
+    }
+
+    pub fn with_betas(mut self, betas: Vec<String>) -> Self {
+        self.betas = betas;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Allows requests to be sent directly from a browser, e.g. from WebAssembly.
+    ///
+    /// This exposes the API key to anyone able to inspect the page, so only enable it for
+    /// user-provided keys or trusted environments.
+    pub fn with_browser_access(mut self, browser_access: bool) -> Self {
+        self.browser_access = browser_access;
+        self
+    }
+
+    pub fn with_default_model(mut self, model: Model) -> Self {
+        self.default_model = Some(model);
+        self
+    }
+
+    /// Appends query parameters to every request, e.g. for gateways expecting a subscription key
+    /// in the query string.
+    pub fn with_default_query(mut self, default_query: Vec<(String, String)>) -> Self {
+        self.default_query = default_query;
+        self
+    }
+
+    /// Maps retired models to their replacement.
+    ///
+    /// When the API answers a message request with a `not_found_error` about the requested model,
+    /// the request is retried once with the mapped model.
+    pub fn with_model_fallbacks(mut self, model_fallbacks: HashMap<Model, Model>) -> Self {
+        self.model_fallbacks = model_fallbacks;
+        self
+    }
+
+    /// Overrides the price of `model`, e.g. for negotiated rates or models unknown to the crate.
+    pub fn with_model_pricing(mut self, model: Model, pricing: ModelPricing) -> Self {
+        self.model_pricing.insert(model, pricing);
+        self
+    }
+
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    pub fn with_max_image_size(mut self, max_image_size: usize) -> Self {
+        self.max_image_size = max_image_size;
+        self
+    }
+
+    pub fn with_max_request_bytes(mut self, max_request_bytes: usize) -> Self {
+        self.max_request_bytes = max_request_bytes;
+        self
+    }
+
+    /// Caps the text streamed by [`Client::stream_message`], independently of `max_tokens`.
+    ///
+    /// Once the limit is exceeded, the text up to the limit is yielded, followed by
+    /// [`AnthropicError::OutputLimitExceeded`], and the connection is dropped.
+    ///
+    /// [`Client::stream_message`]: crate::client::Client::stream_message
+    pub fn with_max_output_chars(mut self, max_output_chars: usize) -> Self {
+        self.max_output_chars = Some(max_output_chars);
+        self
+    }
+
+    /// Records the raw frames of streamed responses, exposed by [`MessageStream::raw_frames`].
+    ///
+    /// [`MessageStream::raw_frames`]: crate::api::stream::MessageStream::raw_frames
+    pub fn with_raw_stream_capture(mut self, raw_stream_capture: bool) -> Self {
+        self.raw_stream_capture = raw_stream_capture;
+        self
+    }
+
+    /// Retries requests failing with a rate limit, overloaded or server error, or which could not
+    /// connect.
+    ///
+    /// The delay of a `retry-after` response header takes precedence over the backoff of
+    /// `retry`. Streams are only retried until their response starts.
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Aborts requests not completed within `timeout`, which includes reading a streamed response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails requests that cannot connect within `timeout`.
+    ///
+    /// Useful with streaming, where a short connect timeout detects unreachable hosts while
+    /// leaving `timeout` unset or long enough to read slow responses.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Loads the configuration from the environment.
+    ///
+    /// `ANTHROPIC_API_KEY` is required, while `ANTHROPIC_BASE_URL`, `ANTHROPIC_VERSION` and
+    /// `ANTHROPIC_API_VERSION` override their defaults when set.
     pub fn from_env() -> Result<Self, AnthropicError> {
-        let api_key = std::env::var(API_KEY_ENV_VAR)
-            .map_err(|_| AnthropicError::MissingApiKey(API_KEY_ENV_VAR))?;
-        Ok(Self::new(api_key))
+        Self::from_env_vars(|name| std::env::var(name).ok())
     }
+
+    fn from_env_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, AnthropicError> {
+        let api_key = var(API_KEY_ENV_VAR).ok_or(AnthropicError::MissingApiKey(API_KEY_ENV_VAR))?;
+        let mut config = Self::new(api_key);
+        if let Some(base_url) = var(BASE_URL_ENV_VAR) {
+            config = config.with_base_url(base_url);
+        }
+        if let Some(version) = var(ANTHROPIC_VERSION_ENV_VAR) {
+            config = config.with_anthropic_version(version.parse()?);
+        }
+        if let Some(version) = var(API_VERSION_ENV_VAR) {
+            config = config.with_api_version(version.parse()?);
+        }
+        Ok(config)
+    }
+
+    /// Loads the configuration from a JSON file, or a TOML file with the `toml` feature.
+    ///
+    /// The API key may be omitted from the file and is taken from the `ANTHROPIC_API_KEY`
+    /// environment variable, which takes precedence over the file when both are set.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AnthropicError> {
+        Self::from_file_with_api_key(path, std::env::var(API_KEY_ENV_VAR).ok())
+    }
+
+    fn from_file_with_api_key(
+        path: impl AsRef<Path>,
+        env_api_key: Option<String>,
+    ) -> Result<Self, AnthropicError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let file: ConfigFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents)?,
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::from_str(&contents)
+                .map_err(|err| AnthropicError::ConfigFile(err.message().to_string()))?,
+            _ => {
+                return Err(AnthropicError::ConfigFile(format!(
+                    "unsupported file format: {}",
+                    path.display()
+                )))
+            }
+        };
+
+        let api_key = env_api_key
+            .or(file.api_key)
+            .ok_or(AnthropicError::MissingApiKey(API_KEY_ENV_VAR))?;
+        let mut config = Self::new(api_key);
+        if let Some(base_url) = file.base_url {
+            config = config.with_base_url(base_url);
+        }
+        if let Some(model) = file.default_model {
+            config = config.with_default_model(model);
+        }
+        Ok(config.with_betas(file.betas))
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("anthropic_version", &self.anthropic_version)
+            .field("api_key", &self.api_key)
+            .field("api_version", &self.api_version)
+            .field("audit_sink", &self.audit_sink.is_some())
+            .field("backend", &self.backend)
+            .field("base_url", &self.base_url)
+            .field("betas", &self.betas)
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("browser_access", &self.browser_access)
+            .field("default_model", &self.default_model)
+            // Values may hold credentials, so only the parameter names are shown.
+            .field(
+                "default_query",
+                &self
+                    .default_query
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("model_fallbacks", &self.model_fallbacks)
+            .field("model_pricing", &self.model_pricing)
+            .field("pool_max_idle_per_host", &self.pool_max_idle_per_host)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("max_image_size", &self.max_image_size)
+            .field("max_request_bytes", &self.max_request_bytes)
+            .field("max_output_chars", &self.max_output_chars)
+            .field("raw_stream_capture", &self.raw_stream_capture)
+            .field("retry", &self.retry)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .finish()
+    }
+}
+
+/// Builder for [`Config`], returned by [`Config::builder`].
+///
+/// Settings left unset keep the defaults of [`Config::new`]:
+/// - `base_url`: `https://api.anthropic.com`
+/// - `anthropic_version`: [`AnthropicVersion::default`]
+/// - `api_version`: [`ApiVersion::default`]
+/// - `timeout`: none, requests may wait indefinitely
+#[derive(Default)]
+pub struct ConfigBuilder {
+    api_key: Option<SecretKey>,
+    base_url: Option<String>,
+    anthropic_version: Option<AnthropicVersion>,
+    api_version: Option<ApiVersion>,
+    timeout: Option<Duration>,
+}
+
+impl ConfigBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(SecretKey::from(api_key.into()));
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn anthropic_version(mut self, version: AnthropicVersion) -> Self {
+        self.anthropic_version = Some(version);
+        self
+    }
+
+    pub fn api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = Some(version);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Builds the configuration, failing if the API key is missing or empty.
+    pub fn build(self) -> Result<Config, AnthropicError> {
+        let api_key = self
+            .api_key
+            .ok_or(AnthropicError::MissingApiKey("api_key"))?;
+        if api_key.expose().is_empty() {
+            return Err(AnthropicError::InvalidParameter {
+                field: "api_key",
+                message: "must not be empty".to_string(),
+            });
+        }
+
+        let mut config = Config::with_secret_key(api_key);
+        if let Some(base_url) = self.base_url {
+            config = config.with_base_url(base_url);
+        }
+        if let Some(version) = self.anthropic_version {
+            config = config.with_anthropic_version(version);
+        }
+        if let Some(version) = self.api_version {
+            config = config.with_api_version(version);
+        }
+        config.timeout = self.timeout;
+        Ok(config)
+    }
+}
+
+/// Subset of [`Config`] that can be loaded from a file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    api_key: Option<String>,
+    base_url: Option<String>,
+    default_model: Option<Model>,
+    betas: Vec<String>,
 }
 
 #[cfg(test)]
@@ -51,15 +496,40 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn should_redact_api_key_in_debug() {
+        let config = Config::new("anthropic-api-key");
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("anthropic-api-key"));
+        assert!(debug.contains("[REDACTED]"));
+    }
+
     #[test]
     fn should_use_default_values() {
         let api_key = "anthropic-api-key";
         let config = Config::new(api_key);
 
         assert_eq!(config.anthropic_version, AnthropicVersion::default());
-        assert_eq!(config.api_key, api_key);
+        assert_eq!(config.api_key(), api_key);
         assert_eq!(config.api_version, ApiVersion::default());
+        assert!(config.audit_sink.is_none());
         assert_eq!(config.base_url, DEFAULT_API_BASE_URL);
+        assert!(!config.browser_access);
+        assert_eq!(config.circuit_breaker, None);
+        assert_eq!(config.default_model, None);
+        assert!(config.default_query.is_empty());
+        assert!(config.model_fallbacks.is_empty());
+        assert_eq!(
+            config.pool_max_idle_per_host,
+            DEFAULT_POOL_MAX_IDLE_PER_HOST
+        );
+        assert_eq!(config.pool_idle_timeout, DEFAULT_POOL_IDLE_TIMEOUT);
+        assert_eq!(config.max_image_size, DEFAULT_MAX_IMAGE_SIZE);
+        assert_eq!(config.max_request_bytes, DEFAULT_MAX_REQUEST_BYTES);
+        assert!(!config.raw_stream_capture);
+        assert_eq!(config.retry, None);
+        assert_eq!(config.timeout, None);
+        assert_eq!(config.connect_timeout, None);
     }
 
     #[test]
@@ -80,4 +550,249 @@ mod tests {
         let config = Config::new(api_key).with_base_url("https://custom.api.anthropic.com");
         assert_eq!(config.base_url, "https://custom.api.anthropic.com");
     }
+
+    #[test]
+    fn should_set_circuit_breaker() {
+        let circuit_breaker = CircuitBreakerConfig::new(3, Duration::from_secs(5));
+        let config = Config::new("anthropic-api-key").with_circuit_breaker(circuit_breaker.clone());
+        assert_eq!(config.circuit_breaker, Some(circuit_breaker));
+    }
+
+    #[test]
+    fn should_set_browser_access() {
+        let config = Config::new("anthropic-api-key").with_browser_access(true);
+        assert!(config.browser_access);
+    }
+
+    #[test]
+    fn should_set_default_model() {
+        let config = Config::new("anthropic-api-key").with_default_model(Model::Claude3Haiku);
+        assert_eq!(config.default_model, Some(Model::Claude3Haiku));
+    }
+
+    #[test]
+    fn should_set_pool_settings() {
+        let api_key = "anthropic-api-key";
+
+        let config = Config::new(api_key)
+            .with_pool_max_idle_per_host(32)
+            .with_pool_idle_timeout(Duration::from_secs(30));
+        assert_eq!(config.pool_max_idle_per_host, 32);
+        assert_eq!(config.pool_idle_timeout, Duration::from_secs(30));
+
+        assert!(crate::client::Client::new(config).is_ok());
+    }
+
+    #[test]
+    fn should_set_max_image_size() {
+        let config = Config::new("anthropic-api-key").with_max_image_size(1024);
+        assert_eq!(config.max_image_size, 1024);
+    }
+
+    #[test]
+    fn should_set_max_request_bytes() {
+        let config = Config::new("anthropic-api-key").with_max_request_bytes(1024);
+        assert_eq!(config.max_request_bytes, 1024);
+    }
+
+    #[test]
+    fn should_set_raw_stream_capture() {
+        let config = Config::new("anthropic-api-key").with_raw_stream_capture(true);
+        assert!(config.raw_stream_capture);
+    }
+
+    #[test]
+    fn should_set_timeout() {
+        let config = Config::new("anthropic-api-key").with_timeout(Duration::from_secs(30));
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+
+        assert!(crate::client::Client::new(config).is_ok());
+    }
+
+    #[test]
+    fn should_set_connect_timeout() {
+        let config = Config::new("anthropic-api-key").with_connect_timeout(Duration::from_secs(5));
+        assert_eq!(config.connect_timeout, Some(Duration::from_secs(5)));
+
+        assert!(crate::client::Client::new(config).is_ok());
+    }
+
+    #[test]
+    fn should_build_config() {
+        let config = Config::builder()
+            .api_key("anthropic-api-key")
+            .base_url("https://custom.api.anthropic.com")
+            .anthropic_version(AnthropicVersion::Initial)
+            .api_version(ApiVersion::V1)
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        assert_eq!(config.api_key(), "anthropic-api-key");
+        assert_eq!(config.base_url, "https://custom.api.anthropic.com");
+        assert_eq!(config.anthropic_version, AnthropicVersion::Initial);
+        assert_eq!(config.api_version, ApiVersion::V1);
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+
+        let config = Config::builder()
+            .api_key("anthropic-api-key")
+            .build()
+            .unwrap();
+        assert_eq!(config.base_url, DEFAULT_API_BASE_URL);
+        assert_eq!(config.anthropic_version, AnthropicVersion::default());
+        assert_eq!(config.timeout, None);
+    }
+
+    #[test]
+    fn should_reject_missing_or_empty_api_key_in_builder() {
+        assert!(matches!(
+            Config::builder().build(),
+            Err(AnthropicError::MissingApiKey(_))
+        ));
+        assert!(matches!(
+            Config::builder().api_key("").build(),
+            Err(AnthropicError::InvalidParameter {
+                field: "api_key",
+                ..
+            })
+        ));
+    }
+
+    fn write_config_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn should_load_config_from_json_file() {
+        let path = write_config_file(
+            "anthropic-rs-config.json",
+            r#"{
+                "api_key": "file-api-key",
+                "base_url": "https://custom.api.anthropic.com",
+                "default_model": "claude-3-haiku-20240307",
+                "betas": ["prompt-caching-2024-07-31", "pdfs-2024-09-25"]
+            }"#,
+        );
+
+        let config = Config::from_file_with_api_key(&path, None).unwrap();
+        assert_eq!(config.api_key(), "file-api-key");
+        assert_eq!(config.base_url, "https://custom.api.anthropic.com");
+        assert_eq!(config.default_model, Some(Model::Claude3Haiku));
+        assert_eq!(
+            config.betas,
+            vec!["prompt-caching-2024-07-31", "pdfs-2024-09-25"]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn should_load_config_from_toml_file() {
+        let path = write_config_file(
+            "anthropic-rs-config.toml",
+            r#"
+                base_url = "https://custom.api.anthropic.com"
+                default_model = "claude-3-opus-20240229"
+                betas = ["prompt-caching-2024-07-31"]
+            "#,
+        );
+
+        let config =
+            Config::from_file_with_api_key(&path, Some("env-api-key".to_string())).unwrap();
+        assert_eq!(config.api_key(), "env-api-key");
+        assert_eq!(config.base_url, "https://custom.api.anthropic.com");
+        assert_eq!(config.default_model, Some(Model::Claude3Opus));
+        assert_eq!(config.betas, vec!["prompt-caching-2024-07-31"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_prefer_env_api_key_over_config_file() {
+        let path = write_config_file(
+            "anthropic-rs-config-env.json",
+            r#"{ "api_key": "file-api-key" }"#,
+        );
+
+        let config =
+            Config::from_file_with_api_key(&path, Some("env-api-key".to_string())).unwrap();
+        assert_eq!(config.api_key(), "env-api-key");
+        assert!(config.betas.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_require_api_key_from_file_or_env() {
+        let path = write_config_file("anthropic-rs-config-no-key.json", r#"{}"#);
+
+        assert!(matches!(
+            Config::from_file_with_api_key(&path, None),
+            Err(AnthropicError::MissingApiKey(API_KEY_ENV_VAR))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn env_vars<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn should_load_config_from_env() {
+        let config = Config::from_env_vars(env_vars(&[
+            ("ANTHROPIC_API_KEY", "env-api-key"),
+            ("ANTHROPIC_BASE_URL", "https://proxy.example.com"),
+            ("ANTHROPIC_VERSION", "2023-01-01"),
+            ("ANTHROPIC_API_VERSION", "v1"),
+        ]))
+        .unwrap();
+
+        assert_eq!(config.api_key(), "env-api-key");
+        assert_eq!(config.base_url, "https://proxy.example.com");
+        assert_eq!(config.anthropic_version, AnthropicVersion::Initial);
+        assert_eq!(config.api_version, ApiVersion::V1);
+    }
+
+    #[test]
+    fn should_default_optional_env_settings() {
+        let config =
+            Config::from_env_vars(env_vars(&[("ANTHROPIC_API_KEY", "env-api-key")])).unwrap();
+
+        assert_eq!(config.base_url, DEFAULT_API_BASE_URL);
+        assert_eq!(config.anthropic_version, AnthropicVersion::Latest);
+        assert_eq!(config.api_version, ApiVersion::V1);
+    }
+
+    #[test]
+    fn should_require_api_key_in_env() {
+        assert!(matches!(
+            Config::from_env_vars(env_vars(&[])),
+            Err(AnthropicError::MissingApiKey(API_KEY_ENV_VAR))
+        ));
+    }
+
+    #[test]
+    fn should_reject_invalid_versions_in_env() {
+        assert!(matches!(
+            Config::from_env_vars(env_vars(&[
+                ("ANTHROPIC_API_KEY", "env-api-key"),
+                ("ANTHROPIC_VERSION", "2024-01-01"),
+            ])),
+            Err(AnthropicError::AnthropicVersion(_))
+        ));
+        assert!(matches!(
+            Config::from_env_vars(env_vars(&[
+                ("ANTHROPIC_API_KEY", "env-api-key"),
+                ("ANTHROPIC_API_VERSION", "v2"),
+            ])),
+            Err(AnthropicError::ApiVersion(_))
+        ));
+    }
 }
@@ -1,35 +1,106 @@
-use std::str::Utf8Error;
+use std::{str::Utf8Error, time::Duration};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::client::ApiVersionError;
+use crate::{
+    client::{AnthropicVersion, AnthropicVersionError, ApiVersionError},
+    models::model::Model,
+    rate_limit::RateLimit,
+};
 
+/// Errors returned by the client.
+///
+/// `Display` output never includes request or response payloads so errors can be logged safely.
 #[derive(Debug, thiserror::Error)]
 pub enum AnthropicError {
     #[error("API error: {0}")]
     Api(ApiErrorResponse),
 
     #[error("HTTP client error: {0}")]
-    Network(#[from] reqwest::Error),
+    Network(reqwest::Error),
+
+    /// The request exceeded the configured `timeout` or `connect_timeout`.
+    #[error("Request timed out: {0}")]
+    Timeout(reqwest::Error),
 
     #[error("API version error: {0}")]
     ApiVersion(#[from] ApiVersionError),
 
+    #[error("Anthropic version error: {0}")]
+    AnthropicVersion(#[from] AnthropicVersionError),
+
     #[error("URL parse error: {0}")]
     UrlParse(String),
 
-    #[error("Failed to deserialize: {0}")]
+    #[error("Failed to deserialize: {}", redact_json_error(.0))]
     JsonDeserialize(#[from] serde_json::Error),
 
+    #[error("Failed to serialize request: {0}")]
+    Serialize(serde_json::Error),
+
+    #[error("Request of {0} bytes exceeds the API size limit")]
+    PayloadTooLarge(usize),
+
     #[error("Invalid header value: {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
 
+    #[error("Invalid parameter {field}: {message}")]
+    InvalidParameter {
+        field: &'static str,
+        message: String,
+    },
+
     #[error("Model not supported: {0}")]
     ModelNotSupported(String),
 
+    #[error("Model {0} requires anthropic-version {1} or later")]
+    UnsupportedAnthropicVersion(Model, AnthropicVersion),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid config file: {0}")]
+    ConfigFile(String),
+
     #[error("Missing API key {0}")]
     MissingApiKey(&'static str),
 
+    #[error("Missing environment variable {0}")]
+    MissingEnvVar(String),
+
+    /// A credentials provider, e.g. a Vertex AI [`AccessTokenProvider`], failed to supply them.
+    ///
+    /// [`AccessTokenProvider`]: crate::backend::vertex::AccessTokenProvider
+    #[error("Failed to obtain credentials: {0}")]
+    Credentials(String),
+
+    #[error("Image exceeds the maximum size of {0} bytes")]
+    ImageTooLarge(usize),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// The endpoint is only available on the Anthropic API, see [`Backend`].
+    ///
+    /// [`Backend`]: crate::backend::Backend
+    #[error("Endpoint {0} is not supported by the configured backend")]
+    UnsupportedByBackend(String),
+
+    #[error("Stream closed before any message was started")]
+    EmptyResponse,
+
+    #[error("Output exceeded the limit of {0} characters")]
+    OutputLimitExceeded(usize),
+
+    #[error("Circuit breaker is open after repeated rate limit errors")]
+    CircuitOpen,
+
+    #[error("Request was cancelled")]
+    Cancelled,
+
+    #[error("Expected a text response but the model stopped to use a tool")]
+    UnexpectedToolUse,
+
     #[error("Invalid Stream Event")]
     InvalidStreamEvent,
 
@@ -40,15 +111,82 @@ pub enum AnthropicError {
     Unexpected(String),
 }
 
+impl AnthropicError {
+    /// Whether the error is a transient failure for which sending the same request again is safe.
+    ///
+    /// Rate limits and server errors are rejected before any processing, and connection failures
+    /// never reach the server. Timeouts are not retryable since they may occur after the request
+    /// was processed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Api(response) => matches!(
+                response.error.error_type,
+                ApiErrorType::RateLimit | ApiErrorType::Unexpected | ApiErrorType::Overloaded
+            ),
+            Self::Network(err) => err.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Type of the error reported by the API, to tell e.g. authentication failures from
+    /// overloaded errors without matching on messages.
+    pub fn api_error_type(&self) -> Option<ApiErrorType> {
+        match self {
+            Self::Api(response) => Some(response.error.error_type),
+            _ => None,
+        }
+    }
+
+    /// Id of the request that failed, when the API reported one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Self::Api(response) => response.request_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AnthropicError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout(err)
+        } else {
+            Self::Network(err)
+        }
+    }
+}
+
+/// `serde_json` errors quote the offending input, which may be user content, so only the
+/// error category and position are kept.
+fn redact_json_error(err: &serde_json::Error) -> String {
+    format!(
+        "{:?} error at line {} column {}",
+        err.classify(),
+        err.line(),
+        err.column()
+    )
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, thiserror::Error)]
 #[error("Error response: {error_type} {error}")]
 pub struct ApiErrorResponse {
     #[serde(rename = "type")]
     pub error_type: String,
     pub error: ApiErrorDetail,
+    /// Delay requested by the `retry-after` header of the response.
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+    /// Rate limits reported by the response, e.g. to throttle subsequent requests.
+    ///
+    /// Boxed to keep [`AnthropicError`] small.
+    #[serde(skip)]
+    pub rate_limit: Option<Box<RateLimit>>,
+    /// Value of the `request-id` header, to share with Anthropic support.
+    #[serde(skip)]
+    pub request_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, thiserror::Error)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, thiserror::Error)]
 #[error("Api error: {error_type} {message}")]
 pub struct ApiErrorDetail {
     #[serde(rename = "type")]
@@ -56,7 +194,8 @@ pub struct ApiErrorDetail {
     pub message: String,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, thiserror::Error)]
+/// Value of the `error.type` field of API errors.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, thiserror::Error)]
 pub enum ApiErrorType {
     #[error("invalid_request_error")]
     #[serde(rename = "invalid_request_error")]
@@ -89,6 +228,11 @@ pub enum ApiErrorType {
     #[error("overloaded_error")]
     #[serde(rename = "overloaded_error")]
     Overloaded,
+
+    /// Error type introduced after this version of the crate.
+    #[error("unknown")]
+    #[serde(other, rename = "unknown")]
+    Unknown,
 }
 
 #[cfg(test)]
@@ -116,4 +260,75 @@ mod tests {
         assert_eq!(&ApiErrorType::Unexpected.to_string(), "api_error");
         assert_eq!(&ApiErrorType::Overloaded.to_string(), "overloaded_error");
     }
+
+    #[test]
+    fn should_only_retry_transient_api_errors() {
+        let api_error = |error_type| {
+            AnthropicError::Api(ApiErrorResponse {
+                error_type: "error".to_string(),
+                error: ApiErrorDetail {
+                    error_type,
+                    message: "message".to_string(),
+                },
+                retry_after: None,
+                rate_limit: None,
+                request_id: None,
+            })
+        };
+
+        assert!(api_error(ApiErrorType::RateLimit).is_retryable());
+        assert!(api_error(ApiErrorType::Overloaded).is_retryable());
+        assert!(api_error(ApiErrorType::Unexpected).is_retryable());
+        assert!(!api_error(ApiErrorType::InvalidRequest).is_retryable());
+        assert!(!api_error(ApiErrorType::RequestTooLarge).is_retryable());
+        assert!(!AnthropicError::PayloadTooLarge(1024).is_retryable());
+    }
+
+    #[test]
+    fn should_expose_api_error_type_and_request_id() {
+        let body = serde_json::json!({
+            "type": "error",
+            "error": { "type": "authentication_error", "message": "invalid x-api-key" },
+        });
+        let err = AnthropicError::Api(ApiErrorResponse {
+            request_id: Some("req_01".to_string()),
+            ..serde_json::from_value(body).unwrap()
+        });
+        assert_eq!(err.api_error_type(), Some(ApiErrorType::Authentication));
+        assert_eq!(err.request_id(), Some("req_01"));
+
+        let err = AnthropicError::PayloadTooLarge(1024);
+        assert_eq!(err.api_error_type(), None);
+        assert_eq!(err.request_id(), None);
+    }
+
+    #[test]
+    fn should_deserialize_unknown_error_type() {
+        let error_type: ApiErrorType =
+            serde_json::from_value(serde_json::json!("billing_error")).unwrap();
+        assert_eq!(error_type, ApiErrorType::Unknown);
+    }
+
+    #[test]
+    fn should_not_display_payload_content() {
+        let secret = "my social security number is 123-45-6789";
+
+        let err = serde_json::from_value::<u32>(serde_json::json!(secret)).unwrap_err();
+        let err = AnthropicError::from(err);
+        assert!(!err.to_string().contains(secret));
+
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": "invalid_request_error",
+                "message": "messages: roles must alternate",
+            },
+            "request": { "messages": [{ "role": "user", "content": secret }] },
+        });
+        let err = AnthropicError::Api(serde_json::from_value(body).unwrap());
+        let rendered = err.to_string();
+        assert!(rendered.contains("invalid_request_error"));
+        assert!(rendered.contains("messages: roles must alternate"));
+        assert!(!rendered.contains(secret));
+    }
 }
@@ -1,12 +1,23 @@
 pub mod api;
+pub mod audit;
+pub mod backend;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod circuit_breaker;
 pub mod client;
 pub mod config;
 pub mod error;
 pub mod models;
+pub mod rate_limit;
+pub mod retry;
 
 pub mod prelude {
     pub use crate::{
-        api::message::{Message, MessageRequest, MessageResponse, Role, TokenUsage},
+        api::{
+            conversation::Conversation,
+            message::{Message, MessageRequest, MessageResponse, Role, Sampling, TokenUsage},
+            tool::Tool,
+        },
         client::{AnthropicVersion, ApiVersion, Client},
         config::Config,
         error::AnthropicError,
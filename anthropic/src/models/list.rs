@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// A model available to the account, as returned by [`Client::list_models`].
+///
+/// The `id` can be turned into a [`Model`] with [`Model::from_id`], which keeps ids unknown to
+/// this crate as [`Model::Custom`].
+///
+/// [`Model`]: crate::models::model::Model
+/// [`Model::from_id`]: crate::models::model::Model::from_id
+/// [`Model::Custom`]: crate::models::model::Model::Custom
+///
+/// [`Client::list_models`]: crate::client::Client::list_models
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Human readable name of the model.
+    pub display_name: String,
+    /// RFC 3339 datetime of the model release.
+    pub created_at: String,
+}
+
+/// A page of models, most recently released first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModelList {
+    pub data: Vec<ModelInfo>,
+    /// Whether more models follow this page.
+    pub has_more: bool,
+    /// Id of the first model of the page, to pass as `before_id` for the previous page.
+    pub first_id: Option<String>,
+    /// Id of the last model of the page, to pass as `after_id` for the next page.
+    pub last_id: Option<String>,
+}
+
+/// Pagination parameters of [`Client::list_models_page`].
+///
+/// [`Client::list_models_page`]: crate::client::Client::list_models_page
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ListModelsParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_id: Option<String>,
+    /// Number of models per page, between 1 and 1000. Defaults to 20.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl ListModelsParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_before_id(mut self, before_id: impl Into<String>) -> Self {
+        self.before_id = Some(before_id.into());
+        self
+    }
+
+    pub fn with_after_id(mut self, after_id: impl Into<String>) -> Self {
+        self.after_id = Some(after_id.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_deserialize_model_list() {
+        let raw = r#"{"data":[{"type":"model","id":"claude-3-5-sonnet-20240620","display_name":"Claude 3.5 Sonnet","created_at":"2024-06-20T00:00:00Z"}],"has_more":false,"first_id":"claude-3-5-sonnet-20240620","last_id":"claude-3-5-sonnet-20240620"}"#;
+        let list: ModelList = serde_json::from_str(raw).unwrap();
+        assert_eq!(
+            list.data,
+            vec![ModelInfo {
+                id: "claude-3-5-sonnet-20240620".to_string(),
+                display_name: "Claude 3.5 Sonnet".to_string(),
+                created_at: "2024-06-20T00:00:00Z".to_string(),
+            }]
+        );
+        assert!(!list.has_more);
+        assert_eq!(list.last_id.as_deref(), Some("claude-3-5-sonnet-20240620"));
+    }
+}
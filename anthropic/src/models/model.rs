@@ -2,51 +2,286 @@ use core::fmt;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+use crate::client::AnthropicVersion;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Model {
     #[serde(rename = "claude-3-5-sonnet-20240620")]
     Claude35Sonnet,
+    #[default]
+    #[serde(rename = "claude-3-5-sonnet-20241022")]
+    Claude35Sonnet20241022,
+    #[serde(rename = "claude-3-5-haiku-20241022")]
+    Claude35Haiku20241022,
+    /// Alias of the most recent Claude 3.5 Sonnet snapshot.
+    #[serde(rename = "claude-3-5-sonnet-latest")]
+    Claude35SonnetLatest,
+    /// Alias of the most recent Claude 3.5 Haiku snapshot.
+    #[serde(rename = "claude-3-5-haiku-latest")]
+    Claude35HaikuLatest,
     #[serde(rename = "claude-3-opus-20240229")]
     Claude3Opus,
     #[serde(rename = "claude-3-sonnet-20240229")]
     Claude3Sonnet,
     #[serde(rename = "claude-3-haiku-20240307")]
     Claude3Haiku,
+    /// Model id unknown to this version of the crate, e.g. the dated snapshot an alias resolved to.
+    ///
+    /// Deserialization, `FromStr` and [`Model::from_id`] all fall back to this variant, so
+    /// responses and configs naming a new model are not rejected.
+    #[serde(untagged)]
+    Custom(String),
 }
 
+/// Context window shared by every supported model.
+const CONTEXT_WINDOW: u32 = 200_000;
+
+/// Release date and approximate training data cutoff of each known model, by model id.
+///
+/// Dates are `YYYY-MM-DD` and cutoffs `YYYY-MM`. Keep this table updated with new releases.
+const MODEL_METADATA: &[(&str, &str, &str)] = &[
+    ("claude-3-5-sonnet-20241022", "2024-10-22", "2024-04"),
+    ("claude-3-5-haiku-20241022", "2024-11-04", "2024-07"),
+    ("claude-3-5-sonnet-20240620", "2024-06-20", "2024-04"),
+    ("claude-3-opus-20240229", "2024-03-04", "2023-08"),
+    ("claude-3-sonnet-20240229", "2024-03-04", "2023-08"),
+    ("claude-3-haiku-20240307", "2024-03-13", "2023-08"),
+];
+
 impl Model {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Claude35Sonnet => "claude-3-5-sonnet-20240620",
+            Self::Claude35Sonnet20241022 => "claude-3-5-sonnet-20241022",
+            Self::Claude35Haiku20241022 => "claude-3-5-haiku-20241022",
+            Self::Claude35SonnetLatest => "claude-3-5-sonnet-latest",
+            Self::Claude35HaikuLatest => "claude-3-5-haiku-latest",
             Self::Claude3Opus => "claude-3-opus-20240229",
             Self::Claude3Sonnet => "claude-3-sonnet-20240229",
             Self::Claude3Haiku => "claude-3-haiku-20240307",
+            Self::Custom(model) => model,
         }
     }
-}
 
-impl Default for Model {
-    fn default() -> Self {
-        Self::Claude35Sonnet
+    /// Dated snapshot the model id refers to, resolving the `-latest` aliases.
+    ///
+    /// Alias targets are the ones known at the time of this crate release, the API may already
+    /// point them to a newer snapshot.
+    pub fn snapshot(&self) -> Model {
+        match self {
+            Self::Claude35SonnetLatest => Self::Claude35Sonnet20241022,
+            Self::Claude35HaikuLatest => Self::Claude35Haiku20241022,
+            model => model.clone(),
+        }
+    }
+
+    /// Model id on Amazon Bedrock, where aliases are not available.
+    ///
+    /// Custom ids are passed through unchanged so Bedrock ids such as cross-region inference
+    /// profiles can be used directly.
+    pub fn bedrock_id(&self) -> String {
+        match self.snapshot() {
+            Self::Claude35Sonnet20241022 => "anthropic.claude-3-5-sonnet-20241022-v2:0".to_string(),
+            Self::Custom(model) => model,
+            model => format!("anthropic.{}-v1:0", model.as_str()),
+        }
+    }
+
+    /// Model id on Google Vertex AI, where aliases are not available and snapshots are separated
+    /// by `@`.
+    ///
+    /// Custom ids are passed through unchanged.
+    pub fn vertex_id(&self) -> String {
+        match self.snapshot() {
+            Self::Claude35Sonnet => "claude-3-5-sonnet@20240620".to_string(),
+            Self::Claude35Sonnet20241022 => "claude-3-5-sonnet-v2@20241022".to_string(),
+            Self::Claude35Haiku20241022 => "claude-3-5-haiku@20241022".to_string(),
+            Self::Claude3Opus => "claude-3-opus@20240229".to_string(),
+            Self::Claude3Sonnet => "claude-3-sonnet@20240229".to_string(),
+            Self::Claude3Haiku => "claude-3-haiku@20240307".to_string(),
+            model => model.as_str().to_string(),
+        }
+    }
+
+    /// Model for `id`, falling back to [`Model::Custom`] for ids unknown to this crate.
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "claude-3-5-sonnet-20240620" => Self::Claude35Sonnet,
+            "claude-3-5-sonnet-20241022" => Self::Claude35Sonnet20241022,
+            "claude-3-5-haiku-20241022" => Self::Claude35Haiku20241022,
+            "claude-3-5-sonnet-latest" => Self::Claude35SonnetLatest,
+            "claude-3-5-haiku-latest" => Self::Claude35HaikuLatest,
+            "claude-3-opus-20240229" => Self::Claude3Opus,
+            "claude-3-sonnet-20240229" => Self::Claude3Sonnet,
+            "claude-3-haiku-20240307" => Self::Claude3Haiku,
+            _ => Self::Custom(id.to_string()),
+        }
+    }
+
+    /// Reads the model id from the environment variable `var`.
+    ///
+    /// Ids unknown to this crate are kept as [`Model::Custom`].
+    pub fn from_env(var: &str) -> Result<Self, crate::error::AnthropicError> {
+        Self::from_env_var(var, |name| std::env::var(name).ok())
+    }
+
+    fn from_env_var(
+        var: &str,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, crate::error::AnthropicError> {
+        let model = lookup(var)
+            .ok_or_else(|| crate::error::AnthropicError::MissingEnvVar(var.to_string()))?;
+        Ok(Self::from_id(&model))
+    }
+
+    /// Maximum number of input tokens the model accepts.
+    ///
+    /// Every supported model shares the same context window, without needing a beta header.
+    /// Custom models are assumed to share it too.
+    pub fn context_window(&self) -> u32 {
+        CONTEXT_WINDOW
+    }
+
+    /// Maximum number of tokens the model can generate in a single response.
+    ///
+    /// The June 2024 [`Model::Claude35Sonnet`] only reaches its 8192 token limit with the
+    /// `max-tokens-3-5-sonnet-2024-07-15` beta header, otherwise it is capped at 4096. Custom
+    /// models get the conservative 4096.
+    pub fn max_output_tokens(&self) -> u32 {
+        match self.snapshot() {
+            Self::Claude35Sonnet
+            | Self::Claude35Sonnet20241022
+            | Self::Claude35Haiku20241022
+            | Self::Claude35SonnetLatest
+            | Self::Claude35HaikuLatest => 8192,
+            Self::Claude3Opus | Self::Claude3Sonnet | Self::Claude3Haiku | Self::Custom(_) => 4096,
+        }
+    }
+
+    /// Oldest `anthropic-version` the model can be used with.
+    ///
+    /// The Claude 3.5 models were only ever documented against `2023-06-01`. Custom models
+    /// are not checked, so they accept any version.
+    pub fn min_anthropic_version(&self) -> AnthropicVersion {
+        match self {
+            Self::Claude35Sonnet
+            | Self::Claude35Sonnet20241022
+            | Self::Claude35Haiku20241022
+            | Self::Claude35SonnetLatest
+            | Self::Claude35HaikuLatest => AnthropicVersion::Latest,
+            Self::Claude3Opus | Self::Claude3Sonnet | Self::Claude3Haiku | Self::Custom(_) => {
+                AnthropicVersion::Initial
+            }
+        }
+    }
+
+    /// Public release date of the model as `YYYY-MM-DD`, unknown for custom models.
+    pub fn release_date(&self) -> Option<&'static str> {
+        self.metadata().map(|(release_date, _)| release_date)
+    }
+
+    /// Approximate end of the model's training data as `YYYY-MM`, unknown for custom models.
+    ///
+    /// Useful to warn users that answers about recent events may be outdated.
+    pub fn training_cutoff(&self) -> Option<&'static str> {
+        self.metadata().map(|(_, training_cutoff)| training_cutoff)
+    }
+
+    fn metadata(&self) -> Option<(&'static str, &'static str)> {
+        let snapshot = self.snapshot();
+        MODEL_METADATA
+            .iter()
+            .find(|(id, _, _)| *id == snapshot.as_str())
+            .map(|(_, release_date, training_cutoff)| (*release_date, *training_cutoff))
+    }
+
+    /// List price of the model in USD, unknown for custom models.
+    pub fn pricing(&self) -> Option<ModelPricing> {
+        let (input, output) = match self.snapshot() {
+            Self::Claude35Sonnet | Self::Claude35Sonnet20241022 | Self::Claude3Sonnet => {
+                (3.0, 15.0)
+            }
+            Self::Claude35Haiku20241022 => (0.8, 4.0),
+            Self::Claude3Opus => (15.0, 75.0),
+            Self::Claude3Haiku => (0.25, 1.25),
+            Self::Claude35SonnetLatest | Self::Claude35HaikuLatest | Self::Custom(_) => {
+                return None
+            }
+        };
+        Some(ModelPricing::new(input, output))
+    }
+
+    /// Rough capability and cost tier of the model family, higher being more capable.
+    ///
+    /// Meant for fallback routing (e.g. Opus, then Sonnet, then Haiku). Models of the same family
+    /// share a rank regardless of release date, so this is not an ordering by recency. Custom
+    /// models have an unknown tier and rank lowest.
+    pub fn tier_rank(&self) -> u8 {
+        match self {
+            Self::Claude3Opus => 3,
+            Self::Claude35Sonnet
+            | Self::Claude35Sonnet20241022
+            | Self::Claude35SonnetLatest
+            | Self::Claude3Sonnet => 2,
+            Self::Claude35Haiku20241022 | Self::Claude35HaikuLatest | Self::Claude3Haiku => 1,
+            Self::Custom(_) => 0,
+        }
     }
 }
 
 impl FromStr for Model {
     type Err = crate::error::AnthropicError;
 
+    /// Never fails: ids unknown to this crate are kept as [`Model::Custom`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "claude-3-5-sonnet-20240620" => Ok(Self::Claude35Sonnet),
-            "claude-3-opus-20240229" => Ok(Self::Claude3Opus),
-            "claude-3-sonnet-20240229" => Ok(Self::Claude3Sonnet),
-            "claude-3-haiku-20240307" => Ok(Self::Claude3Haiku),
-            _ => Err(crate::error::AnthropicError::ModelNotSupported(
-                s.to_string(),
-            )),
+        Ok(Self::from_id(s))
+    }
+}
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl ModelPricing {
+    pub fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+        }
+    }
+
+    /// Cost of a request with `input_tokens`, generating at most `max_output_tokens`.
+    pub fn estimate(&self, input_tokens: u32, max_output_tokens: u32) -> CostEstimate {
+        CostEstimate {
+            input_tokens,
+            input_cost: f64::from(input_tokens) * self.input_per_mtok / 1_000_000.0,
+            max_output_cost: f64::from(max_output_tokens) * self.output_per_mtok / 1_000_000.0,
         }
     }
 }
 
+/// Estimated cost in USD of a request, returned by [`Client::estimate_cost`].
+///
+/// [`Client::estimate_cost`]: crate::client::Client::estimate_cost
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostEstimate {
+    /// Input tokens the estimate is based on.
+    pub input_tokens: u32,
+    pub input_cost: f64,
+    /// Cost of the output if the model generates up to `max_tokens`.
+    pub max_output_cost: f64,
+}
+
+impl CostEstimate {
+    /// Worst case cost of the request.
+    pub fn max_total_cost(&self) -> f64 {
+        self.input_cost + self.max_output_cost
+    }
+}
+
 impl fmt::Display for Model {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.as_str())
@@ -63,6 +298,22 @@ mod tests {
     #[test]
     fn should_serialize_to_correct_model_names() {
         assert_eq!(Model::Claude35Sonnet.as_str(), "claude-3-5-sonnet-20240620",);
+        assert_eq!(
+            Model::Claude35Sonnet20241022.as_str(),
+            "claude-3-5-sonnet-20241022"
+        );
+        assert_eq!(
+            Model::Claude35Haiku20241022.as_str(),
+            "claude-3-5-haiku-20241022"
+        );
+        assert_eq!(
+            Model::Claude35SonnetLatest.as_str(),
+            "claude-3-5-sonnet-latest"
+        );
+        assert_eq!(
+            Model::Claude35HaikuLatest.as_str(),
+            "claude-3-5-haiku-latest"
+        );
         assert_eq!(Model::Claude3Opus.as_str(), "claude-3-opus-20240229");
         assert_eq!(Model::Claude3Sonnet.as_str(), "claude-3-sonnet-20240229");
         assert_eq!(Model::Claude3Haiku.as_str(), "claude-3-haiku-20240307");
@@ -74,6 +325,22 @@ mod tests {
             Model::Claude35Sonnet,
             Model::from_str("claude-3-5-sonnet-20240620").unwrap(),
         );
+        assert_eq!(
+            Model::Claude35Sonnet20241022,
+            Model::from_str("claude-3-5-sonnet-20241022").unwrap(),
+        );
+        assert_eq!(
+            Model::Claude35Haiku20241022,
+            Model::from_str("claude-3-5-haiku-20241022").unwrap(),
+        );
+        assert_eq!(
+            Model::Claude35SonnetLatest,
+            Model::from_str("claude-3-5-sonnet-latest").unwrap(),
+        );
+        assert_eq!(
+            Model::Claude35HaikuLatest,
+            Model::from_str("claude-3-5-haiku-latest").unwrap(),
+        );
         assert_eq!(
             Model::Claude3Opus,
             Model::from_str("claude-3-opus-20240229").unwrap(),
@@ -89,10 +356,200 @@ mod tests {
     }
 
     #[test]
-    fn should_return_error_for_invalid_model() {
+    fn should_deserialize_unknown_model_as_custom() {
+        let model: Model = serde_json::from_str(r#""claude-3-7-sonnet-20250219""#).unwrap();
+        assert_eq!(
+            model,
+            Model::Custom("claude-3-7-sonnet-20250219".to_string())
+        );
+        assert_eq!(model.as_str(), "claude-3-7-sonnet-20250219");
+        assert_eq!(
+            serde_json::to_string(&model).unwrap(),
+            r#""claude-3-7-sonnet-20250219""#
+        );
+
+        let model: Model = serde_json::from_str(r#""claude-3-haiku-20240307""#).unwrap();
+        assert_eq!(model, Model::Claude3Haiku);
+    }
+
+    #[test]
+    fn should_display_custom_model_id() {
+        let model = Model::Custom("claude-4-preview".to_string());
+        assert_eq!(model.to_string(), "claude-4-preview");
+
+        let response: crate::api::message::MessageResponse = serde_json::from_str(r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-4-preview","content":[],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}"#).unwrap();
+        assert_eq!(response.model, model);
+    }
+
+    #[test]
+    fn should_serialize_model_with_serde_rename() {
+        for model in [
+            Model::Claude35Sonnet20241022,
+            Model::Claude35Haiku20241022,
+            Model::Claude35SonnetLatest,
+            Model::Claude35HaikuLatest,
+        ] {
+            let json = serde_json::to_string(&model).unwrap();
+            assert_eq!(json, format!("\"{}\"", model.as_str()));
+            assert_eq!(serde_json::from_str::<Model>(&json).unwrap(), model);
+        }
+    }
+
+    #[test]
+    fn should_resolve_alias_snapshot() {
+        assert_eq!(Model::default(), Model::Claude35Sonnet20241022);
+        assert_eq!(
+            Model::Claude35SonnetLatest.snapshot(),
+            Model::Claude35Sonnet20241022
+        );
+        assert_eq!(
+            Model::Claude35HaikuLatest.snapshot(),
+            Model::Claude35Haiku20241022
+        );
+        assert_eq!(Model::Claude3Opus.snapshot(), Model::Claude3Opus);
+        assert_eq!(
+            Model::Claude35HaikuLatest.release_date(),
+            Some("2024-11-04")
+        );
+        assert_eq!(Model::Claude35Haiku20241022.max_output_tokens(), 8192);
+    }
+
+    #[test]
+    fn should_return_bedrock_id() {
+        assert_eq!(
+            Model::Claude3Haiku.bedrock_id(),
+            "anthropic.claude-3-haiku-20240307-v1:0"
+        );
+        assert_eq!(
+            Model::Claude35SonnetLatest.bedrock_id(),
+            "anthropic.claude-3-5-sonnet-20241022-v2:0"
+        );
+        assert_eq!(
+            Model::Custom("us.anthropic.claude-3-haiku-20240307-v1:0".to_string()).bedrock_id(),
+            "us.anthropic.claude-3-haiku-20240307-v1:0"
+        );
+    }
+
+    #[test]
+    fn should_return_vertex_id() {
+        assert_eq!(Model::Claude3Opus.vertex_id(), "claude-3-opus@20240229");
+        assert_eq!(
+            Model::Claude35SonnetLatest.vertex_id(),
+            "claude-3-5-sonnet-v2@20241022"
+        );
+        assert_eq!(
+            Model::Custom("claude-3-7-sonnet@20250219".to_string()).vertex_id(),
+            "claude-3-7-sonnet@20250219"
+        );
+    }
+
+    #[test]
+    fn should_estimate_cost_from_pricing() {
+        let pricing = Model::Claude3Opus.pricing().unwrap();
+        assert_eq!(pricing, ModelPricing::new(15.0, 75.0));
+        assert_eq!(Model::Custom("claude-next".to_string()).pricing(), None);
+
+        let estimate = pricing.estimate(200_000, 4_000);
+        assert_eq!(estimate.input_cost, 3.0);
+        assert_eq!(estimate.max_output_cost, 0.3);
+        assert_eq!(estimate.max_total_cost(), 3.3);
+    }
+
+    #[test]
+    fn should_return_context_window() {
+        let models = [
+            Model::Claude35Sonnet,
+            Model::Claude35Sonnet20241022,
+            Model::Claude35Haiku20241022,
+            Model::Claude35SonnetLatest,
+            Model::Claude35HaikuLatest,
+            Model::Claude3Opus,
+            Model::Claude3Sonnet,
+            Model::Claude3Haiku,
+            Model::Custom("claude-unknown".to_string()),
+        ];
+        for model in models {
+            assert_eq!(model.context_window(), 200_000, "{model}");
+        }
+    }
+
+    #[test]
+    fn should_return_max_output_tokens() {
+        assert_eq!(Model::Claude35Sonnet.max_output_tokens(), 8192);
+        assert_eq!(Model::Claude3Opus.max_output_tokens(), 4096);
+        assert_eq!(Model::Claude3Sonnet.max_output_tokens(), 4096);
+        assert_eq!(Model::Claude3Haiku.max_output_tokens(), 4096);
+    }
+
+    #[test]
+    fn should_return_model_metadata() {
+        assert_eq!(Model::Claude35Sonnet.release_date(), Some("2024-06-20"));
+        assert_eq!(Model::Claude35Sonnet.training_cutoff(), Some("2024-04"));
+        assert_eq!(Model::Claude3Haiku.release_date(), Some("2024-03-13"));
+        assert_eq!(Model::Claude3Haiku.training_cutoff(), Some("2023-08"));
+
+        let model = Model::Custom("claude-next".to_string());
+        assert_eq!(model.release_date(), None);
+        assert_eq!(model.training_cutoff(), None);
+    }
+
+    #[test]
+    fn should_rank_models_by_tier() {
+        assert!(Model::Claude3Opus.tier_rank() > Model::Claude35Sonnet.tier_rank());
+        assert!(Model::Claude35Sonnet.tier_rank() > Model::Claude3Haiku.tier_rank());
+        assert_eq!(
+            Model::Claude35Sonnet.tier_rank(),
+            Model::Claude3Sonnet.tier_rank()
+        );
+
+        let mut models = vec![
+            Model::Claude3Haiku,
+            Model::Claude3Opus,
+            Model::Claude3Sonnet,
+        ];
+        models.sort_by_key(|model| std::cmp::Reverse(model.tier_rank()));
+        assert_eq!(
+            models,
+            vec![
+                Model::Claude3Opus,
+                Model::Claude3Sonnet,
+                Model::Claude3Haiku
+            ]
+        );
+    }
+
+    #[test]
+    fn should_read_model_from_env() {
+        let lookup = |name: &str| match name {
+            "MODEL" => Some("claude-3-haiku-20240307".to_string()),
+            "UNKNOWN_MODEL" => Some("claude-unknown".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            Model::from_env_var("MODEL", lookup).unwrap(),
+            Model::Claude3Haiku
+        );
+        assert_eq!(
+            Model::from_env_var("UNKNOWN_MODEL", lookup).unwrap(),
+            Model::Custom("claude-unknown".to_string())
+        );
         assert!(matches!(
-            Model::from_str("claude-invalid-model"),
-            Err(AnthropicError::ModelNotSupported(_))
+            Model::from_env_var("MISSING_MODEL", lookup),
+            Err(AnthropicError::MissingEnvVar(var)) if var == "MISSING_MODEL"
         ));
     }
+
+    #[test]
+    fn should_fall_back_to_custom_model() {
+        assert_eq!(
+            Model::from_str("claude-unknown-model").unwrap(),
+            Model::Custom("claude-unknown-model".to_string())
+        );
+        assert_eq!(
+            Model::from_id("claude-unknown-model"),
+            Model::Custom("claude-unknown-model".to_string())
+        );
+        assert_eq!(Model::from_id("claude-3-opus-20240229"), Model::Claude3Opus);
+    }
 }
@@ -0,0 +1,80 @@
+use reqwest::header::HeaderMap;
+
+/// Snapshot of the `anthropic-ratelimit-*` headers of a response.
+///
+/// Fields are `None` when the matching header is missing or malformed. Reset times are kept as
+/// the RFC 3339 timestamps sent by the API.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests_limit: Option<u64>,
+    pub requests_remaining: Option<u64>,
+    pub requests_reset: Option<String>,
+    pub tokens_limit: Option<u64>,
+    pub tokens_remaining: Option<u64>,
+    pub tokens_reset: Option<String>,
+}
+
+impl RateLimit {
+    /// Reads the rate limit headers, or returns `None` if the response has none of them.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let number = |name: &str| text(name).and_then(|value| value.trim().parse().ok());
+
+        let rate_limit = Self {
+            requests_limit: number("anthropic-ratelimit-requests-limit"),
+            requests_remaining: number("anthropic-ratelimit-requests-remaining"),
+            requests_reset: text("anthropic-ratelimit-requests-reset"),
+            tokens_limit: number("anthropic-ratelimit-tokens-limit"),
+            tokens_remaining: number("anthropic-ratelimit-tokens-remaining"),
+            tokens_reset: text("anthropic-ratelimit-tokens-reset"),
+        };
+        (rate_limit != Self::default()).then_some(rate_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn should_read_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RateLimit::from_headers(&headers), None);
+
+        headers.insert(
+            "anthropic-ratelimit-requests-limit",
+            HeaderValue::from_static("50"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-requests-remaining",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-requests-reset",
+            HeaderValue::from_static("2024-10-15T12:00:30Z"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-remaining",
+            HeaderValue::from_static("not a number"),
+        );
+
+        assert_eq!(
+            RateLimit::from_headers(&headers),
+            Some(RateLimit {
+                requests_limit: Some(50),
+                requests_remaining: Some(0),
+                requests_reset: Some("2024-10-15T12:00:30Z".to_string()),
+                tokens_limit: None,
+                tokens_remaining: None,
+                tokens_reset: None,
+            })
+        );
+    }
+}
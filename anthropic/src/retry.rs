@@ -0,0 +1,165 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// Exponential backoff settings for retried requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Maximum number of retries after the initial attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every subsequent retry.
+    pub base_delay: Duration,
+    /// Upper bound of a single delay.
+    pub max_delay: Duration,
+    /// Whether to randomize delays to spread out concurrent retries.
+    pub jitter: bool,
+}
+
+impl RetryConfig {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delay before the retry numbered `attempt` (starting at 0), without jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Delays before each of the `max_retries` retries, without jitter.
+    pub fn delays(&self) -> Vec<Duration> {
+        (0..self.max_retries)
+            .map(|attempt| self.backoff(attempt))
+            .collect()
+    }
+
+    /// Delay before the retry numbered `attempt`, with jitter applied when enabled.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        if self.jitter {
+            jittered(backoff, random_unit())
+        } else {
+            backoff
+        }
+    }
+
+    /// Sleeps before the retry numbered `attempt`.
+    ///
+    /// Relies on `tokio::time`, so tests can drive it with a paused clock.
+    pub async fn wait(&self, attempt: u32) {
+        tokio::time::sleep(self.delay(attempt)).await;
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            jitter: true,
+        }
+    }
+}
+
+/// Keeps half of `backoff` and randomizes the other half with `sample` in `[0, 1]`.
+fn jittered(backoff: Duration, sample: f64) -> Duration {
+    let half = backoff / 2;
+    half + half.mul_f64(sample.clamp(0.0, 1.0))
+}
+
+/// Cheap random number in `[0, 1)` from the randomly seeded std hasher.
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_use_default_values() {
+        let config = RetryConfig::default();
+        assert_eq!(config.max_retries, DEFAULT_MAX_RETRIES);
+        assert_eq!(config.base_delay, DEFAULT_BASE_DELAY);
+        assert_eq!(config.max_delay, DEFAULT_MAX_DELAY);
+        assert!(config.jitter);
+    }
+
+    #[test]
+    fn should_compute_capped_exponential_delays() {
+        let config = RetryConfig::new(3)
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(300))
+            .with_jitter(false);
+
+        assert_eq!(
+            config.delays(),
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(300),
+            ]
+        );
+        assert_eq!(config.delay(2), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn should_keep_jittered_delay_within_bounds() {
+        let backoff = Duration::from_millis(200);
+        assert_eq!(jittered(backoff, 0.0), Duration::from_millis(100));
+        assert_eq!(jittered(backoff, 1.0), Duration::from_millis(200));
+
+        let config = RetryConfig::new(3).with_base_delay(Duration::from_millis(200));
+        for attempt in 0..config.max_retries {
+            let delay = config.delay(attempt);
+            assert!(delay >= config.backoff(attempt) / 2);
+            assert!(delay <= config.backoff(attempt));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_wait_for_backoff_delay() {
+        let config = RetryConfig::new(3)
+            .with_base_delay(Duration::from_millis(100))
+            .with_jitter(false);
+
+        let start = tokio::time::Instant::now();
+        for attempt in 0..config.max_retries {
+            config.wait(attempt).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(700));
+    }
+}
@@ -1,5 +1,5 @@
 use anthropic_rs::{
-    api::message::{Content, ContentType, Message, MessageRequest, Role},
+    api::message::{Message, MessageRequest},
     client::Client,
     config::Config,
     models::model::Model,
@@ -7,22 +7,14 @@ use anthropic_rs::{
 
 #[tokio::main]
 async fn main() {
-    let api_key = std::env::var("ANTHROPIC_API_KEY")
-        .expect("environment variable ANTHROPIC_API_KEY should be defined");
-
-    let config = Config::new(api_key);
+    let config =
+        Config::from_env().expect("environment variable ANTHROPIC_API_KEY should be defined");
     let client = Client::new(config).unwrap();
 
     let message = MessageRequest {
         model: Model::Claude35Sonnet,
         max_tokens: 1024,
-        messages: vec![Message {
-            role: Role::User,
-            content: vec![Content {
-                content_type: ContentType::Text,
-                text: "Explain the theory of relativity".to_string(),
-            }],
-        }],
+        messages: vec![Message::user("Explain the theory of relativity")],
         ..Default::default()
     };
 
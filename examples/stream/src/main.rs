@@ -1,6 +1,6 @@
 use anthropic_rs::{
     api::{
-        message::{Content, ContentType, Message, MessageRequest, Role},
+        message::{Message, MessageRequest},
         stream::StreamEvent,
     },
     client::Client,
@@ -12,30 +12,28 @@ use std::io::Write;
 
 #[tokio::main]
 async fn main() {
-    let api_key = std::env::var("ANTHROPIC_API_KEY")
-        .expect("environment variable ANTHROPIC_API_KEY should be defined");
-
-    let config = Config::new(api_key);
+    let config =
+        Config::from_env().expect("environment variable ANTHROPIC_API_KEY should be defined");
     let client = Client::new(config).unwrap();
 
     let message = MessageRequest {
         model: Model::Claude35Sonnet,
         stream: true,
         max_tokens: 1024,
-        messages: vec![Message {
-            role: Role::User,
-            content: vec![Content {
-                content_type: ContentType::Text,
-                text: "Explain the theory of relativity".to_string(),
-            }],
-        }],
+        messages: vec![Message::user("Explain the theory of relativity")],
         ..Default::default()
     };
 
     let mut stream = client.stream_message(message.clone()).await.unwrap();
 
     while let Some(event) = stream.next().await {
-        let event = event.unwrap();
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                eprintln!("\nStream failed: {err}");
+                break;
+            }
+        };
         match event {
             StreamEvent::ContentBlockDelta(content) => {
                 print!("{}", content.delta.text);